@@ -32,6 +32,13 @@ const OPUS_INCLUDES: &[&str] = &["include", "silk", "silk/float", "silk/fixed",
 /// Directory where the libopus sources are; relative to `Cargo.toml`
 const OPUS_RFC8251: &str = "opus-rfc8251";
 
+/// pkg-config module / vcpkg port name for a system-installed libopus
+const OPUS_SYSTEM_NAME: &str = "opus";
+
+/// Name of the `CARGO_FEATURE_*` variable (with the prefix already stripped) that forces
+/// compiling the vendored sources even when a system libopus could be found
+const BUNDLED_FEATURE: &str = "BUNDLED";
+
 /// Regular expression matching all functions to bind to
 const OPUS_FUNCS_REGEXP: &str =
     "^((([dr]e)?normalise|(un)?quant|_?ce?lt|alg|compute|ec|kiss|opus|pitch|silk|stereo)_.*|\
@@ -108,6 +115,49 @@ where
         .map(move |file| opus_rfc8251.join(file))
 }
 
+/// Attempts to locate an already-installed libopus, linking against it if found.
+///
+/// Tries `pkg-config` first, falling back to `vcpkg` (resolving its target triple, e.g.
+/// `arm64-osx` or `x64-windows-static`, from `CARGO_CFG_TARGET_OS`/`CARGO_CFG_TARGET_ARCH`) on
+/// Windows and macOS, where `pkg-config` is rarely set up for system libraries. On success, the
+/// probing crate itself emits the `cargo:rustc-link-lib`/`cargo:rustc-link-search` directives;
+/// this only needs to return the compiler arguments bindgen needs to find the headers.
+///
+/// Returns `None` if no system library could be found, in which case the vendored sources should
+/// be compiled instead.
+fn find_system() -> Option<Vec<OsString>> {
+    use std::env::var;
+
+    let target_os = var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let include_paths = if target_os == "windows" || target_os == "macos" {
+        let arch = match &*var("CARGO_CFG_TARGET_ARCH").unwrap_or_default() {
+            "x86_64" => "x64".to_owned(),
+            "aarch64" => "arm64".to_owned(),
+            arch => arch.to_owned(),
+        };
+        let os = if target_os == "macos" {
+            "osx"
+        } else {
+            "windows-static"
+        };
+
+        vcpkg::Config::new()
+            .target_triplet(format!("{}-{}", arch, os))
+            .probe(OPUS_SYSTEM_NAME)
+            .ok()?
+            .include_paths
+    } else {
+        pkg_config::probe_library(OPUS_SYSTEM_NAME).ok()?.include_paths
+    };
+
+    Some(
+        include_paths
+            .into_iter()
+            .flat_map(|path| vec![OsString::from("-I"), path.into_os_string()])
+            .collect(),
+    )
+}
+
 /// Builds libopus
 fn build<T>(opus_rfc8251: T, features: Vec<String>) -> Vec<OsString>
 where
@@ -206,7 +256,14 @@ fn main() {
     // find libopus source directory
     let opus_rfc8251 = manifest_dir.join(OPUS_RFC8251);
 
-    // build libopus & generate bindings
-    let cc_args = build(&opus_rfc8251, features);
+    // prefer a system libopus unless the `bundled` feature forces the vendored sources
+    let system = if features.iter().any(|feature| feature == BUNDLED_FEATURE) {
+        None
+    } else {
+        find_system()
+    };
+
+    // build libopus (if no system library was found or usable) & generate bindings
+    let cc_args = system.unwrap_or_else(|| build(&opus_rfc8251, features));
     generate_bindings(opus_rfc8251, cc_args, output_dir.join(BINDINGS_FILENAME));
 }
@@ -1,11 +1,16 @@
 //! Audio channel control and mapping.
 use crate::slice_ext::{BoundsError, SliceExt};
-use std::{
+use core::{
     convert::TryFrom,
-    error::Error,
     fmt::{self, Display, Formatter},
 };
 
+#[cfg(feature = "std")]
+use std::error::Error;
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{vec, vec::Vec};
+
 /// The error type returned when a channel layout is malformed.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub enum ChannelLayoutError {
@@ -20,6 +25,11 @@ pub enum ChannelLayoutError {
     /// There are either zero streams, too many streams, or the number of coupled streams exceeds
     /// the total number of streams.
     IllegalStreams,
+    /// A channel-mapping index pointed outside the range of internal (decoder-produced) channels.
+    MappingIndexOutOfRange,
+    /// The number of packets decoded for a multistream packet didn't match the channel-mapping
+    /// table's stream count.
+    StreamCountMismatch,
 }
 
 impl Display for ChannelLayoutError {
@@ -31,10 +41,17 @@ impl Display for ChannelLayoutError {
                 "invalid number of channels for the specified family"
             }
             ChannelLayoutError::IllegalStreams => "illegal stream specification",
+            ChannelLayoutError::MappingIndexOutOfRange => {
+                "channel-mapping index pointed outside the decoded channels"
+            }
+            ChannelLayoutError::StreamCountMismatch => {
+                "decoded packet count didn't match the channel-mapping table's stream count"
+            }
         })
     }
 }
 
+#[cfg(feature = "std")]
 impl Error for ChannelLayoutError {}
 
 impl From<BoundsError> for ChannelLayoutError {
@@ -43,6 +60,32 @@ impl From<BoundsError> for ChannelLayoutError {
     }
 }
 
+/// A named speaker position (or Ambisonic component) that a single output channel is assigned
+/// to, for mapping families whose channel order the specification fixes.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum SpeakerPosition {
+    /// Front left.
+    FrontLeft,
+    /// Front center.
+    FrontCenter,
+    /// Front right.
+    FrontRight,
+    /// Side (left of center, behind the front stage) left.
+    SideLeft,
+    /// Side (right of center, behind the front stage) right.
+    SideRight,
+    /// Rear left.
+    RearLeft,
+    /// Rear right.
+    RearRight,
+    /// Rear center.
+    RearCenter,
+    /// Low-frequency effects.
+    LFE,
+    /// The Ambisonic Channel Number (ACN) of an Ambisonics sound field component.
+    Ambisonic(u8),
+}
+
 /// RTP-style channel mapping layouts.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub enum RtpChannelLayout {
@@ -64,6 +107,18 @@ impl TryFrom<u8> for RtpChannelLayout {
     }
 }
 
+impl RtpChannelLayout {
+    /// Returns the speaker position of each output channel, in channel order.
+    pub fn speaker_positions(self) -> Vec<SpeakerPosition> {
+        match self {
+            RtpChannelLayout::Mono => vec![SpeakerPosition::FrontCenter],
+            RtpChannelLayout::Stereo => {
+                vec![SpeakerPosition::FrontLeft, SpeakerPosition::FrontRight]
+            }
+        }
+    }
+}
+
 impl seal::Sealed for RtpChannelLayout {}
 
 impl MappingTable for RtpChannelLayout {
@@ -115,6 +170,163 @@ impl TryFrom<u8> for VorbisChannelLayout {
     }
 }
 
+impl VorbisChannelLayout {
+    /// Returns the speaker position of each output channel, in channel order.
+    pub(crate) fn speaker_positions(self) -> Vec<SpeakerPosition> {
+        use SpeakerPosition::{
+            FrontCenter, FrontLeft, FrontRight, RearCenter, RearLeft, RearRight, SideLeft,
+            SideRight, LFE,
+        };
+
+        match self {
+            VorbisChannelLayout::Mono => vec![FrontCenter],
+            VorbisChannelLayout::Stereo => vec![FrontLeft, FrontRight],
+            VorbisChannelLayout::LinearSurround => vec![FrontLeft, FrontCenter, FrontRight],
+            VorbisChannelLayout::Quadraphonic => vec![FrontLeft, FrontRight, RearLeft, RearRight],
+            VorbisChannelLayout::FivePointZero => {
+                vec![FrontLeft, FrontCenter, FrontRight, RearLeft, RearRight]
+            }
+            VorbisChannelLayout::FivePointOne => {
+                vec![FrontLeft, FrontCenter, FrontRight, RearLeft, RearRight, LFE]
+            }
+            VorbisChannelLayout::SixPointOne => {
+                vec![
+                    FrontLeft,
+                    FrontCenter,
+                    FrontRight,
+                    SideLeft,
+                    SideRight,
+                    RearCenter,
+                    LFE,
+                ]
+            }
+            VorbisChannelLayout::SevenPointOne => {
+                vec![
+                    FrontLeft,
+                    FrontCenter,
+                    FrontRight,
+                    SideLeft,
+                    SideRight,
+                    RearLeft,
+                    RearRight,
+                    LFE,
+                ]
+            }
+        }
+    }
+
+    /// The ITU-R BS.775 fold-down gain applied to center and surround channels when downmixing
+    /// to stereo.
+    const FOLD_DOWN: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+    /// Adds `gain * channel[t]` into `dst[t]` for each sample `t`.
+    fn add_channel(dst: &mut [f32], channel: &[f32], gain: f32) {
+        for (sample, &source) in dst.iter_mut().zip(channel) {
+            *sample += gain * source;
+        }
+    }
+
+    /// Downmixes `input` (one slice of samples per channel, in this layout's documented speaker
+    /// order) to `target`, writing one sample buffer per output channel into `out`.
+    ///
+    /// Uses the standard ITU-R BS.775 fold-down coefficients; an LFE channel, where present, is
+    /// dropped rather than folded in. The summed result is clamped to `[-1.0, 1.0]` to avoid
+    /// clipping.
+    pub(crate) fn downmix(&self, target: RtpChannelLayout, input: &[&[f32]], out: &mut [Vec<f32>]) {
+        let samples = input.first().map_or(0, |channel| channel.len());
+        let mut left = vec![0.0; samples];
+        let mut right = vec![0.0; samples];
+
+        match self {
+            VorbisChannelLayout::Mono => {
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut right, input[0], 1.0);
+            }
+            VorbisChannelLayout::Stereo => {
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut right, input[1], 1.0);
+            }
+            VorbisChannelLayout::LinearSurround => {
+                // left, center, right
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut left, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[2], 1.0);
+                Self::add_channel(&mut right, input[1], Self::FOLD_DOWN);
+            }
+            VorbisChannelLayout::Quadraphonic => {
+                // front left, front right, rear left, rear right
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut left, input[2], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[1], 1.0);
+                Self::add_channel(&mut right, input[3], Self::FOLD_DOWN);
+            }
+            VorbisChannelLayout::FivePointZero => {
+                // front left, front center, front right, rear left, rear right
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut left, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut left, input[3], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[2], 1.0);
+                Self::add_channel(&mut right, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[4], Self::FOLD_DOWN);
+            }
+            VorbisChannelLayout::FivePointOne => {
+                // front left, front center, front right, rear left, rear right, LFE (dropped)
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut left, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut left, input[3], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[2], 1.0);
+                Self::add_channel(&mut right, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[4], Self::FOLD_DOWN);
+            }
+            VorbisChannelLayout::SixPointOne => {
+                // front left, front center, front right, side left, side right, rear center,
+                // LFE (dropped)
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut left, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut left, input[3], Self::FOLD_DOWN);
+                Self::add_channel(&mut left, input[5], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[2], 1.0);
+                Self::add_channel(&mut right, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[4], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[5], Self::FOLD_DOWN);
+            }
+            VorbisChannelLayout::SevenPointOne => {
+                // front left, front center, front right, side left, side right, rear left,
+                // rear right, LFE (dropped)
+                Self::add_channel(&mut left, input[0], 1.0);
+                Self::add_channel(&mut left, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut left, input[3], Self::FOLD_DOWN);
+                Self::add_channel(&mut left, input[5], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[2], 1.0);
+                Self::add_channel(&mut right, input[1], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[4], Self::FOLD_DOWN);
+                Self::add_channel(&mut right, input[6], Self::FOLD_DOWN);
+            }
+        }
+
+        for sample in left.iter_mut().chain(right.iter_mut()) {
+            *sample = sample.max(-1.0).min(1.0);
+        }
+
+        match target {
+            RtpChannelLayout::Stereo => {
+                out[0].clear();
+                out[0].extend_from_slice(&left);
+                out[1].clear();
+                out[1].extend_from_slice(&right);
+            }
+            RtpChannelLayout::Mono => {
+                out[0].clear();
+                out[0].extend(
+                    left.iter()
+                        .zip(&right)
+                        .map(|(l, r)| ((l + r) * 0.5).max(-1.0).min(1.0)),
+                );
+            }
+        }
+    }
+}
+
 /// Ambisonics channel mapping layouts.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub(crate) enum AmbisonicsChannelLayout {
@@ -220,6 +432,61 @@ impl TryFrom<u8> for AmbisonicsChannelLayout {
     }
 }
 
+impl AmbisonicsChannelLayout {
+    /// Returns the speaker position of each output channel, in channel order: an `Ambisonic`
+    /// component for each ambisonic channel, followed, for a non-diegetic layout, by the
+    /// non-diegetic stereo pair.
+    pub(crate) fn speaker_positions(self) -> Vec<SpeakerPosition> {
+        let non_diegetic = self.non_diegetic();
+        let ambisonic_channels = self as u8 - if non_diegetic { 2 } else { 0 };
+
+        let mut positions: Vec<SpeakerPosition> = (0..ambisonic_channels)
+            .map(SpeakerPosition::Ambisonic)
+            .collect();
+        if non_diegetic {
+            positions.push(SpeakerPosition::FrontLeft);
+            positions.push(SpeakerPosition::FrontRight);
+        }
+        positions
+    }
+
+    /// Whether this layout appends a non-diegetic stereo pair after the ambisonic channels.
+    fn non_diegetic(self) -> bool {
+        match self {
+            AmbisonicsChannelLayout::ZeroNonDiegetic
+            | AmbisonicsChannelLayout::OneNonDiegetic
+            | AmbisonicsChannelLayout::TwoNonDiegetic
+            | AmbisonicsChannelLayout::ThreeNonDiegetic
+            | AmbisonicsChannelLayout::FourNonDiegetic
+            | AmbisonicsChannelLayout::FiveNonDiegetic
+            | AmbisonicsChannelLayout::SixNonDiegetic
+            | AmbisonicsChannelLayout::SevenNonDiegetic
+            | AmbisonicsChannelLayout::EightNonDiegetic
+            | AmbisonicsChannelLayout::NineNonDiegetic
+            | AmbisonicsChannelLayout::TenNonDiegetic
+            | AmbisonicsChannelLayout::ElevenNonDiegetic
+            | AmbisonicsChannelLayout::TwelveNonDiegetic
+            | AmbisonicsChannelLayout::ThirteenNonDiegetic
+            | AmbisonicsChannelLayout::FourteenNonDiegetic => true,
+            AmbisonicsChannelLayout::Zero
+            | AmbisonicsChannelLayout::One
+            | AmbisonicsChannelLayout::Two
+            | AmbisonicsChannelLayout::Three
+            | AmbisonicsChannelLayout::Four
+            | AmbisonicsChannelLayout::Five
+            | AmbisonicsChannelLayout::Six
+            | AmbisonicsChannelLayout::Seven
+            | AmbisonicsChannelLayout::Eight
+            | AmbisonicsChannelLayout::Nine
+            | AmbisonicsChannelLayout::Ten
+            | AmbisonicsChannelLayout::Eleven
+            | AmbisonicsChannelLayout::Twelve
+            | AmbisonicsChannelLayout::Thirteen
+            | AmbisonicsChannelLayout::Fourteen => false,
+        }
+    }
+}
+
 mod seal {
     pub trait Sealed {}
 }
@@ -233,6 +500,28 @@ pub trait MappingTable: seal::Sealed {
     ///
     /// [`MappingTable::streams`]: #method.streams
     fn coupled(&self) -> u8;
+
+    /// Routes decoded per-stream channels into final output channel order.
+    ///
+    /// `internal` holds one slice of samples per internal (decoder-output) channel—coupled
+    /// streams yield two, uncoupled streams one—and `out` holds one sample buffer per output
+    /// channel, sized to match.
+    ///
+    /// The default implementation passes `internal` straight through as `out`, appropriate for
+    /// mapping families with no reordering, such as the RTP-style mapping.
+    ///
+    /// Returns [`ChannelLayoutError::MappingIndexOutOfRange`] rather than panicking if a mapping
+    /// implementation is ever asked to route a channel index outside `internal`'s range.
+    ///
+    /// [`ChannelLayoutError::MappingIndexOutOfRange`]: enum.ChannelLayoutError.html#variant.MappingIndexOutOfRange
+    fn route(&self, internal: &[&[f32]], out: &mut [Vec<f32>]) -> Result<(), ChannelLayoutError> {
+        for (row, &channel) in out.iter_mut().zip(internal) {
+            row.clear();
+            row.extend_from_slice(channel);
+        }
+
+        Ok(())
+    }
 }
 
 /// Channel Mapping table as defined in RFC 7845
@@ -263,6 +552,17 @@ impl StandardMappingTable {
             mapping: table.get_res(2..2 + usize::from(channels))?.to_owned(),
         })
     }
+
+    /// Serializes this mapping table back to its on-wire byte layout: the stream count, the
+    /// coupled count, then the channel mapping bytes, in the format parsed by
+    /// [`StandardMappingTable::new`].
+    ///
+    /// [`StandardMappingTable::new`]: #method.new
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        buf.push(self.streams);
+        buf.push(self.coupled);
+        buf.extend_from_slice(&self.mapping);
+    }
 }
 
 impl seal::Sealed for StandardMappingTable {}
@@ -275,6 +575,25 @@ impl MappingTable for StandardMappingTable {
     fn coupled(&self) -> u8 {
         self.coupled
     }
+
+    fn route(&self, internal: &[&[f32]], out: &mut [Vec<f32>]) -> Result<(), ChannelLayoutError> {
+        let samples = internal.first().map_or(0, |channel| channel.len());
+
+        for (row, &index) in out.iter_mut().zip(&self.mapping) {
+            row.clear();
+            match index {
+                // 255 marks a channel as silent, rather than an index into `internal`
+                255 => row.resize(samples, 0.0),
+                index => row.extend_from_slice(
+                    internal
+                        .get(usize::from(index))
+                        .ok_or(ChannelLayoutError::MappingIndexOutOfRange)?,
+                ),
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Ambisonics channel mapping table (for mapping type 3)
@@ -290,7 +609,10 @@ pub struct AmbisonicsMappingTable {
 }
 
 impl AmbisonicsMappingTable {
-    pub fn new(channels: u8, table: &[u8]) -> Result<Self, ChannelLayoutError> {
+    /// `channels` is the number of ambisonic output channels; if `non_diegetic` is set, the
+    /// mapping carries two more output channels beyond that for a non-diegetic stereo pair, which
+    /// aren't represented in the demixing matrix itself.
+    pub fn new(channels: u8, table: &[u8], non_diegetic: bool) -> Result<Self, ChannelLayoutError> {
         use byteorder::{ByteOrder, LE};
 
         let streams = *table.get_res(0)?;
@@ -301,16 +623,78 @@ impl AmbisonicsMappingTable {
             return Err(ChannelLayoutError::IllegalStreams);
         }
 
+        let matrix_channels = if non_diegetic {
+            usize::from(channels).saturating_sub(2)
+        } else {
+            usize::from(channels)
+        };
+        let internal_channels = usize::from(streams) + usize::from(coupled);
+
         Ok(AmbisonicsMappingTable {
             streams,
             coupled,
             matrix: table
-                .get_res(2..2 + (2 * usize::from(channels)))?
+                .get_res(2..2 + (2 * matrix_channels * internal_channels))?
                 .chunks_exact(2)
                 .map(LE::read_u16)
                 .collect(),
         })
     }
+
+    /// Serializes this mapping table back to its on-wire byte layout: the stream count, the
+    /// coupled count, then the LE-encoded demixing matrix, in the format parsed by
+    /// [`AmbisonicsMappingTable::new`].
+    ///
+    /// [`AmbisonicsMappingTable::new`]: #method.new
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        use byteorder::{ByteOrder, LE};
+
+        buf.push(self.streams);
+        buf.push(self.coupled);
+        for &value in &self.matrix {
+            let mut bytes = [0; 2];
+            LE::write_u16(&mut bytes, value);
+            buf.extend_from_slice(&bytes);
+        }
+    }
+
+    /// Applies the [RFC 8486] demixing matrix to `internal`, writing demixed ambisonic samples
+    /// into `out`.
+    ///
+    /// `internal` holds one slice of samples per internal (decoder-output) channel: exactly
+    /// `streams() + coupled()` of them, the last two of which are a non-diegetic stereo pair
+    /// instead of an ambisonic channel if this mapping carries one. `out` holds one sample buffer
+    /// per output channel, sized to match: the ambisonic channels produced by the matrix, plus,
+    /// again, two more for a non-diegetic pair.
+    ///
+    /// A non-diegetic stereo pair is never passed through the matrix—its two internal channels
+    /// are copied into the final two output channels untouched.
+    ///
+    /// [RFC 8486]: https://tools.ietf.org/html/rfc8486
+    pub fn demix(&self, internal: &[&[f32]], out: &mut [Vec<f32>]) {
+        let internal_channels = usize::from(self.streams) + usize::from(self.coupled);
+        let matrix_channels = self.matrix.len() / internal_channels;
+        let samples = internal.first().map_or(0, |channel| channel.len());
+
+        for (i, row) in out.iter_mut().enumerate().take(matrix_channels) {
+            row.clear();
+            row.extend((0..samples).map(|t| {
+                (0..internal_channels)
+                    .map(|j| {
+                        let coefficient = self.matrix[i * internal_channels + j] as i16 as f32;
+                        (coefficient / 32768.0) * internal[j][t]
+                    })
+                    .sum()
+            }));
+        }
+
+        // a non-diegetic stereo pair is carried as the final two internal/output channels, passed
+        // through untouched rather than demixed
+        for (channel, row) in out.iter_mut().skip(matrix_channels).enumerate().take(2) {
+            row.clear();
+            row.extend_from_slice(internal[internal_channels - 2 + channel]);
+        }
+    }
 }
 
 impl seal::Sealed for AmbisonicsMappingTable {}
@@ -323,6 +707,11 @@ impl MappingTable for AmbisonicsMappingTable {
     fn coupled(&self) -> u8 {
         self.coupled
     }
+
+    fn route(&self, internal: &[&[f32]], out: &mut [Vec<f32>]) -> Result<(), ChannelLayoutError> {
+        self.demix(internal, out);
+        Ok(())
+    }
 }
 
 /// The channel mapping family and channel layout for an Ogg Opus stream.
@@ -372,10 +761,11 @@ impl ChannelMapping {
                 layout: AmbisonicsChannelLayout::try_from(channels)?,
                 mapping: StandardMappingTable::new(channels, table)?,
             }),
-            3 => Ok(ChannelMapping::AmbisonicsDemixed {
-                layout: AmbisonicsChannelLayout::try_from(channels)?,
-                mapping: AmbisonicsMappingTable::new(channels, table)?,
-            }),
+            3 => {
+                let layout = AmbisonicsChannelLayout::try_from(channels)?;
+                let mapping = AmbisonicsMappingTable::new(channels, table, layout.non_diegetic())?;
+                Ok(ChannelMapping::AmbisonicsDemixed { layout, mapping })
+            }
             255 => Ok(ChannelMapping::Discrete {
                 channels,
                 mapping: StandardMappingTable::new(channels, table)?,
@@ -393,4 +783,220 @@ impl ChannelMapping {
             ChannelMapping::Discrete { ref mapping, .. } => mapping,
         }
     }
+
+    /// Returns the total number of output channels, the value written as the channel count byte
+    /// alongside this mapping in an `OpusHead` packet.
+    pub(crate) fn channels(&self) -> u8 {
+        match self {
+            ChannelMapping::RTP(layout) => *layout as u8,
+            ChannelMapping::Vorbis { layout, .. } => *layout as u8,
+            ChannelMapping::AmbisonicsIndividual { layout, .. } => *layout as u8,
+            ChannelMapping::AmbisonicsDemixed { layout, .. } => *layout as u8,
+            ChannelMapping::Discrete { channels, .. } => *channels,
+        }
+    }
+
+    /// Returns the speaker position of each output channel, in channel order, or `None` for a
+    /// discrete-channel mapping, whose channel semantics the specification leaves
+    /// application-defined.
+    pub(crate) fn speaker_positions(&self) -> Option<Vec<SpeakerPosition>> {
+        match self {
+            ChannelMapping::RTP(layout) => Some(layout.speaker_positions()),
+            ChannelMapping::Vorbis { layout, .. } => Some(layout.speaker_positions()),
+            ChannelMapping::AmbisonicsIndividual { layout, .. } => Some(layout.speaker_positions()),
+            ChannelMapping::AmbisonicsDemixed { layout, .. } => Some(layout.speaker_positions()),
+            ChannelMapping::Discrete { .. } => None,
+        }
+    }
+
+    /// Downmixes routed, speaker-ordered output channels to an RTP-style mono/stereo `target`,
+    /// per ITU-R BS.775.
+    ///
+    /// Only a Vorbis-family mapping has fold-down coefficients defined; any other family returns
+    /// [`ChannelLayoutError::BadChannelsForFamily`].
+    ///
+    /// [`ChannelLayoutError::BadChannelsForFamily`]: enum.ChannelLayoutError.html#variant.BadChannelsForFamily
+    pub(crate) fn downmix(
+        &self,
+        target: RtpChannelLayout,
+        input: &[&[f32]],
+        out: &mut [Vec<f32>],
+    ) -> Result<(), ChannelLayoutError> {
+        match self {
+            ChannelMapping::Vorbis { layout, .. } => {
+                layout.downmix(target, input, out);
+                Ok(())
+            }
+            _ => Err(ChannelLayoutError::BadChannelsForFamily),
+        }
+    }
+
+    /// Serializes this channel mapping back to its on-wire byte layout, the inverse of
+    /// [`ChannelMapping::new`]: the channel mapping family byte, followed—for any family other
+    /// than 0 (RTP), which carries no mapping table on the wire—by the stream count, coupled
+    /// count, and mapping table or demixing matrix.
+    ///
+    /// [`ChannelMapping::new`]: #method.new
+    pub(crate) fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            ChannelMapping::RTP(_) => buf.push(0),
+            ChannelMapping::Vorbis { mapping, .. } => {
+                buf.push(1);
+                mapping.write(buf);
+            }
+            ChannelMapping::AmbisonicsIndividual { mapping, .. } => {
+                buf.push(2);
+                mapping.write(buf);
+            }
+            ChannelMapping::AmbisonicsDemixed { mapping, .. } => {
+                buf.push(3);
+                mapping.write(buf);
+            }
+            ChannelMapping::Discrete { mapping, .. } => {
+                buf.push(255);
+                mapping.write(buf);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_mapping_table_new_rejects_zero_streams() {
+        assert_eq!(
+            StandardMappingTable::new(2, &[0, 0, 0, 1]).unwrap_err(),
+            ChannelLayoutError::IllegalStreams
+        );
+    }
+
+    #[test]
+    fn standard_mapping_table_new_rejects_more_coupled_than_streams() {
+        assert_eq!(
+            StandardMappingTable::new(2, &[1, 2, 0, 1]).unwrap_err(),
+            ChannelLayoutError::IllegalStreams
+        );
+    }
+
+    #[test]
+    fn standard_mapping_table_write_round_trips_new() {
+        let table = StandardMappingTable::new(2, &[1, 0, 0, 1]).unwrap();
+        let mut buf = Vec::new();
+        table.write(&mut buf);
+        assert_eq!(buf, vec![1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn standard_mapping_table_route_reorders_and_silences_channels() {
+        // 2 output channels: channel 0 takes internal stream 1, channel 1 is silent (255)
+        let table = StandardMappingTable::new(2, &[2, 0, 1, 255]).unwrap();
+        let internal: [&[f32]; 2] = [&[1.0, 2.0], &[3.0, 4.0]];
+        let mut out = vec![Vec::new(), Vec::new()];
+        table.route(&internal, &mut out).unwrap();
+        assert_eq!(out[0], vec![3.0, 4.0]);
+        assert_eq!(out[1], vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn standard_mapping_table_route_rejects_out_of_range_index() {
+        let table = StandardMappingTable::new(1, &[1, 0, 5]).unwrap();
+        let internal: [&[f32]; 1] = [&[1.0]];
+        let mut out = vec![Vec::new()];
+        assert_eq!(
+            table.route(&internal, &mut out).unwrap_err(),
+            ChannelLayoutError::MappingIndexOutOfRange
+        );
+    }
+
+    #[test]
+    fn ambisonics_mapping_table_demix_applies_q15_coefficients() {
+        // streams=1, coupled=0, single Q15 coefficient 16384 (== 0.5)
+        let table = AmbisonicsMappingTable::new(1, &[1, 0, 0x00, 0x40], false).unwrap();
+        let internal: [&[f32]; 1] = [&[4.0, 8.0]];
+        let mut out = vec![Vec::new()];
+        table.demix(&internal, &mut out);
+        assert_eq!(out[0], vec![2.0, 4.0]);
+    }
+
+    #[test]
+    fn ambisonics_mapping_table_demix_passes_non_diegetic_pair_through() {
+        // streams=3, coupled=0: 3 internal channels total, matching internal_channels ==
+        // internal.len() the way a real caller (which builds `internal` via
+        // multipacket::Decoder::deinterleave) actually produces it. 1 ambisonic output channel
+        // plus a non-diegetic stereo pair means the matrix has a single row (channels - 2) over
+        // all 3 columns, with zero coefficients for the last two (non-diegetic) columns.
+        let table = AmbisonicsMappingTable::new(
+            3,
+            &[3, 0, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00],
+            true,
+        )
+        .unwrap();
+        let internal: [&[f32]; 3] = [&[4.0], &[7.0], &[9.0]];
+        let mut out = vec![Vec::new(), Vec::new(), Vec::new()];
+        table.demix(&internal, &mut out);
+        assert_eq!(out[0], vec![2.0]);
+        assert_eq!(out[1], vec![7.0]);
+        assert_eq!(out[2], vec![9.0]);
+    }
+
+    #[test]
+    fn rtp_channel_layout_route_passes_internal_channels_through() {
+        let layout = RtpChannelLayout::Stereo;
+        let internal: [&[f32]; 2] = [&[1.0], &[2.0]];
+        let mut out = vec![Vec::new(), Vec::new()];
+        layout.route(&internal, &mut out).unwrap();
+        assert_eq!(out[0], vec![1.0]);
+        assert_eq!(out[1], vec![2.0]);
+    }
+
+    #[test]
+    fn vorbis_channel_layout_downmix_folds_center_and_surrounds_to_stereo() {
+        // front left, front center, front right, rear left, rear right
+        let layout = VorbisChannelLayout::FivePointZero;
+        let internal: [&[f32]; 5] = [&[0.25], &[0.25], &[0.25], &[0.25], &[0.25]];
+        let mut out = vec![Vec::new(), Vec::new()];
+        layout.downmix(RtpChannelLayout::Stereo, &internal, &mut out);
+        let expected =
+            0.25 + VorbisChannelLayout::FOLD_DOWN * 0.25 + VorbisChannelLayout::FOLD_DOWN * 0.25;
+        assert_eq!(out[0], vec![expected]);
+        assert_eq!(out[1], vec![expected]);
+    }
+
+    #[test]
+    fn vorbis_channel_layout_downmix_averages_to_mono() {
+        let layout = VorbisChannelLayout::Stereo;
+        let internal: [&[f32]; 2] = [&[1.0], &[-1.0]];
+        let mut out = vec![Vec::new()];
+        layout.downmix(RtpChannelLayout::Mono, &internal, &mut out);
+        assert_eq!(out[0], vec![0.0]);
+    }
+
+    #[test]
+    fn channel_mapping_downmix_rejects_non_vorbis_families() {
+        let mapping = ChannelMapping::RTP(RtpChannelLayout::Stereo);
+        let internal: [&[f32]; 2] = [&[1.0], &[1.0]];
+        let mut out = vec![Vec::new(), Vec::new()];
+        assert_eq!(
+            mapping
+                .downmix(RtpChannelLayout::Stereo, &internal, &mut out)
+                .unwrap_err(),
+            ChannelLayoutError::BadChannelsForFamily
+        );
+    }
+
+    #[test]
+    fn channel_mapping_downmix_dispatches_to_vorbis_layout() {
+        let mapping = ChannelMapping::Vorbis {
+            layout: VorbisChannelLayout::Stereo,
+            mapping: StandardMappingTable::new(2, &[1, 0, 0, 1]).unwrap(),
+        };
+        let internal: [&[f32]; 2] = [&[0.25], &[0.75]];
+        let mut out = vec![Vec::new()];
+        mapping
+            .downmix(RtpChannelLayout::Mono, &internal, &mut out)
+            .unwrap();
+        assert_eq!(out[0], vec![0.5]);
+    }
 }
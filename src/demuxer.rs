@@ -0,0 +1,487 @@
+//! Sans-IO, `no_std`-compatible push-based Ogg Opus demuxing.
+//!
+//! Unlike [`crate::ogg::OggOpusReader`], [`Demuxer`] does not own a reader and never seeks: bytes
+//! are pushed in as they arrive (over a socket, from flash, wherever) and fully-assembled Opus
+//! packets are pulled back out once enough of the container has been buffered. This keeps its own
+//! small amount of page-reassembly logic separate from the blocking, `std`-only
+//! [`crate::ogg::OggOpusReader`] rather than retrofitting it, the same way `zstd-rs` splits its
+//! blocking and `no_std` I/O into separate shims.
+//!
+//! [`crate::ogg::OggOpusReader`]: ../ogg/struct.OggOpusReader.html
+#![cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+
+use crate::{
+    channel::ChannelMapping,
+    error::{Error, Result},
+    io::{Read, ReadError},
+    multipacket::Multipacket,
+    packet::{Frame, Multistream, Packet as OpusPacket},
+    slice_ext::SliceExt,
+};
+use core::fmt::{self, Display, Formatter};
+
+#[cfg(feature = "std")]
+use std::{collections::VecDeque, error};
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+/// The error type returned when the pushed container bytes are malformed.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum DemuxerError {
+    /// Either of the Identification Header or the Comment Header had the wrong magic number.
+    BadMagic,
+}
+
+impl Display for DemuxerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DemuxerError::BadMagic => "invalid magic number",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for DemuxerError {}
+
+/// The 4-byte pattern that begins every Ogg page.
+const CAPTURE_PATTERN: [u8; 4] = *b"OggS";
+
+/// Length, in bytes, of an Ogg page header up to (not including) its segment table.
+const PAGE_HEADER_LEN: usize = 27;
+
+/// Human-readable codec identification for the identification header packet.
+const ID_HEADER_MAGIC: [u8; 8] = *b"OpusHead";
+
+/// Human-readable codec identification for the comment header packet.
+const COMMENT_HEADER_MAGIC: [u8; 8] = *b"OpusTags";
+
+/// A push-based, incremental Ogg Opus demuxer.
+///
+/// Bytes are appended with [`Demuxer::push`] as they become available; [`Demuxer::next_packet`],
+/// [`Demuxer::next_frame`], and [`Demuxer::next_multipacket`] return `Some` once a full Opus
+/// packet has been reassembled from the pages buffered so far, and `None` when more input is
+/// needed.
+#[derive(Debug)]
+pub struct Demuxer {
+    /// Container bytes not yet parsed into a complete page.
+    buf: Vec<u8>,
+    /// Packet bytes accumulated so far for a packet that spans multiple pages.
+    pending_packet: Vec<u8>,
+    /// The number of header/audio packets reassembled so far, used to recognize the first two
+    /// (the identification and comment headers) as they arrive.
+    packets_seen: u64,
+    /// The channel mapping parsed from the identification header, once seen.
+    channels: Option<ChannelMapping>,
+    /// The number of samples (at 48 kHz) to discard from the start of decoded output, parsed from
+    /// the identification header.
+    pre_skip: u16,
+    /// The granule position of the last complete page parsed, or `0` before the first one.
+    granule: u64,
+    /// Fully-assembled audio packets, awaiting [`Demuxer::next_packet`].
+    packets: VecDeque<Vec<u8>>,
+    /// Frames left over from the last packet handed to [`Demuxer::next_frame`].
+    frame_cache: Vec<Frame>,
+}
+
+impl Demuxer {
+    /// Creates a new, empty `Demuxer`.
+    pub fn new() -> Demuxer {
+        Demuxer {
+            buf: Vec::new(),
+            pending_packet: Vec::new(),
+            packets_seen: 0,
+            channels: None,
+            pre_skip: 0,
+            granule: 0,
+            packets: VecDeque::new(),
+            frame_cache: Vec::new(),
+        }
+    }
+
+    /// Returns the channel mapping parsed from the identification header, or `None` if it hasn't
+    /// been pushed yet.
+    pub fn channels(&self) -> Option<&ChannelMapping> {
+        self.channels.as_ref()
+    }
+
+    /// Returns the number of samples (at 48 kHz) to discard from the start of decoded output, or
+    /// `0` if the identification header hasn't been pushed yet.
+    pub fn pre_skip(&self) -> u16 {
+        self.pre_skip
+    }
+
+    /// Returns the granule position of the last complete page parsed, or `0` if none has been yet.
+    ///
+    /// Subtracting [`Demuxer::pre_skip`] from this gives the number of samples that will have been
+    /// decoded, excluding priming samples, once every packet assembled so far is decoded.
+    pub fn granule(&self) -> u64 {
+        self.granule
+    }
+
+    /// Appends `data` to the internal buffer, reassembling any complete pages it finishes.
+    pub fn push(&mut self, data: &[u8]) -> Result<()> {
+        self.buf.extend_from_slice(data);
+
+        while let Some(consumed) = self.try_parse_page()? {
+            self.buf.drain(..consumed);
+        }
+
+        Ok(())
+    }
+
+    /// Parses a single complete page from the front of `self.buf`, returning the number of bytes
+    /// it occupied, or `None` if `self.buf` doesn't yet hold a complete page.
+    fn try_parse_page(&mut self) -> Result<Option<usize>> {
+        if self.buf.len() < PAGE_HEADER_LEN {
+            return Ok(None);
+        }
+        if self.buf[..4] != CAPTURE_PATTERN {
+            return Err(DemuxerError::BadMagic.into());
+        }
+
+        let segments = usize::from(self.buf[26]);
+        if self.buf.len() < PAGE_HEADER_LEN + segments {
+            return Ok(None);
+        }
+
+        let segment_table = self.buf[PAGE_HEADER_LEN..PAGE_HEADER_LEN + segments].to_vec();
+        let body_len: usize = segment_table.iter().copied().map(usize::from).sum();
+        let total_len = PAGE_HEADER_LEN + segments + body_len;
+        if self.buf.len() < total_len {
+            return Ok(None);
+        }
+
+        let mut granule_bytes = [0; 8];
+        granule_bytes.copy_from_slice(&self.buf[6..14]);
+        self.granule = u64::from_le_bytes(granule_bytes);
+
+        let mut offset = PAGE_HEADER_LEN + segments;
+        for &seg in &segment_table {
+            self.pending_packet
+                .extend_from_slice(&self.buf[offset..offset + usize::from(seg)]);
+            offset += usize::from(seg);
+
+            // a lacing value below 255 ends the packet; 255 means it continues on the next page
+            if seg < 255 {
+                let packet = core::mem::replace(&mut self.pending_packet, Vec::new());
+                self.on_packet(packet)?;
+            }
+        }
+
+        Ok(Some(total_len))
+    }
+
+    /// Handles one fully-reassembled packet: the first two are the identification and comment
+    /// headers, and the rest are queued as audio packets.
+    fn on_packet(&mut self, packet: Vec<u8>) -> Result<()> {
+        self.packets_seen += 1;
+
+        match self.packets_seen {
+            1 => {
+                if packet.get(..8) != Some(&ID_HEADER_MAGIC[..]) {
+                    return Err(DemuxerError::BadMagic.into());
+                }
+                self.channels = Some(Self::parse_channels(&packet)?);
+                self.pre_skip = Self::parse_pre_skip(&packet)?;
+            }
+            2 => {
+                if packet.get(..8) != Some(&COMMENT_HEADER_MAGIC[..]) {
+                    return Err(DemuxerError::BadMagic.into());
+                }
+                // the comment header's tags aren't exposed by `Demuxer` yet; only its magic
+                // number is validated so packet assembly can continue
+            }
+            _ => self.packets.push_back(packet),
+        }
+
+        Ok(())
+    }
+
+    /// Parses the channel mapping family byte and table out of an identification header packet.
+    fn parse_channels(data: &[u8]) -> Result<ChannelMapping> {
+        ChannelMapping::new(*data.get_res(9)?, *data.get_res(18)?, data.get_res(19..)?)
+            .map_err(Error::from)
+    }
+
+    /// Parses the pre-skip field out of an identification header packet.
+    fn parse_pre_skip(data: &[u8]) -> Result<u16> {
+        let mut pre_skip = [0; 2];
+        pre_skip.copy_from_slice(data.get_res(10..=11)?);
+        Ok(u16::from_le_bytes(pre_skip))
+    }
+
+    /// Returns the next fully-assembled Opus packet, or `None` if one hasn't been buffered yet.
+    pub fn next_packet(&mut self) -> Option<Result<OpusPacket>> {
+        let data = self.packets.pop_front()?;
+        Some(OpusPacket::new(&data[..]))
+    }
+
+    /// Returns the next fully-assembled multistream packet, ready for [`multipacket::Decoder`], or
+    /// `None` if one hasn't been buffered yet.
+    ///
+    /// [`multipacket::Decoder`]: ../multipacket/struct.Decoder.html
+    pub fn next_multipacket(&mut self) -> Option<Result<Multipacket>> {
+        let channels = self.channels.as_ref()?;
+        let data = self.packets.pop_front()?;
+
+        Some(Multipacket::new(&data[..], channels.mapping_table()))
+    }
+
+    /// Returns the next decoded frame, or `None` if a full packet hasn't been buffered yet.
+    pub fn next_frame(&mut self) -> Option<Result<Frame>> {
+        if self.frame_cache.is_empty() {
+            let channels = self.channels.as_ref()?;
+            let data = self.packets.pop_front()?;
+
+            self.frame_cache = match Multistream::new(&data[..], channels.mapping_table()) {
+                Ok(multistream) => multistream.frames().collect(),
+                Err(err) => return Some(Err(err)),
+            };
+            // built in chronological order, but drained back-to-front with `pop`
+            self.frame_cache.reverse();
+        }
+
+        Some(Ok(self.frame_cache.pop()?))
+    }
+}
+
+impl Default for Demuxer {
+    fn default() -> Demuxer {
+        Demuxer::new()
+    }
+}
+
+/// Pulls bytes from a [`crate::io::Read`] source and drives a [`Demuxer`] over them.
+///
+/// Unlike [`Demuxer`] itself, `Reader` owns its source and reads exactly as many bytes as each Ogg
+/// page header says it needs—never more, and never requiring [`std::io::Seek`]—so it works over
+/// any forward-only, `no_std`-compatible byte source, not just a seekable `std` one.
+///
+/// [`std::io::Seek`]: https://doc.rust-lang.org/std/io/trait.Seek.html
+pub struct Reader<R> {
+    reader: R,
+    demuxer: Demuxer,
+}
+
+impl<R> fmt::Debug for Reader<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("reader", &"..")
+            .field("demuxer", &self.demuxer)
+            .finish()
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new `Reader` pulling an Ogg Opus container from `reader`.
+    pub fn new(reader: R) -> Reader<R> {
+        Reader {
+            reader,
+            demuxer: Demuxer::new(),
+        }
+    }
+
+    /// Returns the channel mapping parsed from the identification header, or `None` if it hasn't
+    /// been read yet.
+    pub fn channels(&self) -> Option<&ChannelMapping> {
+        self.demuxer.channels()
+    }
+
+    /// Returns the number of samples (at 48 kHz) to discard from the start of decoded output, or
+    /// `0` if the identification header hasn't been read yet.
+    pub fn pre_skip(&self) -> u16 {
+        self.demuxer.pre_skip()
+    }
+
+    /// Returns the granule position of the last complete page read, or `0` if none has been yet.
+    pub fn granule(&self) -> u64 {
+        self.demuxer.granule()
+    }
+
+    /// Reads and reassembles exactly one page from `self.reader`, feeding it to `self.demuxer`.
+    ///
+    /// Returns `true` if a page was read, or `false` at a clean end of stream (the source ran out
+    /// right at a page boundary, with no partial page in progress).
+    fn read_page(&mut self) -> Result<bool> {
+        let mut capture = [0; 4];
+        match self.reader.read_exact(&mut capture) {
+            Ok(()) => {}
+            Err(ReadError::UnexpectedEof) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut rest = [0; PAGE_HEADER_LEN - 4];
+        self.reader.read_exact(&mut rest)?;
+        let segments = usize::from(rest[22]);
+
+        let mut segment_table = vec![0; segments];
+        self.reader.read_exact(&mut segment_table)?;
+        let body_len: usize = segment_table.iter().copied().map(usize::from).sum();
+
+        let mut body = vec![0; body_len];
+        self.reader.read_exact(&mut body)?;
+
+        let mut page = Vec::with_capacity(capture.len() + rest.len() + segments + body_len);
+        page.extend_from_slice(&capture);
+        page.extend_from_slice(&rest);
+        page.extend_from_slice(&segment_table);
+        page.extend_from_slice(&body);
+
+        self.demuxer.push(&page)?;
+        Ok(true)
+    }
+
+    /// Returns the next fully-assembled Opus packet, reading further pages as needed, or `None`
+    /// once the source is exhausted with no packet left to return.
+    pub fn next_packet(&mut self) -> Option<Result<OpusPacket>> {
+        loop {
+            if let Some(packet) = self.demuxer.next_packet() {
+                return Some(packet);
+            }
+            match self.read_page() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    /// Returns the next fully-assembled multistream packet, reading further pages as needed, or
+    /// `None` once the source is exhausted with no packet left to return.
+    pub fn next_multipacket(&mut self) -> Option<Result<Multipacket>> {
+        loop {
+            if let Some(multipacket) = self.demuxer.next_multipacket() {
+                return Some(multipacket);
+            }
+            match self.read_page() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    /// Returns the next decoded frame, reading further pages as needed, or `None` once the source
+    /// is exhausted with no frame left to return.
+    pub fn next_frame(&mut self) -> Option<Result<Frame>> {
+        loop {
+            if let Some(frame) = self.demuxer.next_frame() {
+                return Some(frame);
+            }
+            match self.read_page() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal identification header packet: mono, RTP-family channel mapping, 312 samples of
+    /// pre-skip.
+    fn id_header() -> Vec<u8> {
+        let mut data = vec![0u8; 19];
+        data[..8].copy_from_slice(&ID_HEADER_MAGIC);
+        data[8] = 1; // version
+        data[9] = 1; // channels
+        data[10..12].copy_from_slice(&312u16.to_le_bytes());
+        data[12..16].copy_from_slice(&48_000u32.to_le_bytes());
+        data[18] = 0; // channel mapping family (RTP)
+        data
+    }
+
+    /// A minimal comment header packet; `Demuxer` only validates its magic number.
+    fn comment_header() -> Vec<u8> {
+        COMMENT_HEADER_MAGIC.to_vec()
+    }
+
+    /// Builds a single Ogg page carrying `packets` in order, laced by RFC 3533's segment table.
+    fn make_page(granule: u64, packets: &[&[u8]]) -> Vec<u8> {
+        let mut segment_table = Vec::new();
+        let mut body = Vec::new();
+        for packet in packets {
+            let mut remaining = packet.len();
+            while remaining >= 255 {
+                segment_table.push(255);
+                remaining -= 255;
+            }
+            segment_table.push(remaining as u8);
+            body.extend_from_slice(packet);
+        }
+
+        let mut page = Vec::new();
+        page.extend_from_slice(&CAPTURE_PATTERN);
+        page.push(0); // version
+        page.push(0); // header type
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 4]); // serial
+        page.extend_from_slice(&[0; 4]); // page sequence
+        page.extend_from_slice(&[0; 4]); // checksum (unvalidated by Demuxer)
+        page.push(segment_table.len() as u8);
+        page.extend_from_slice(&segment_table);
+        page.extend_from_slice(&body);
+        page
+    }
+
+    #[test]
+    fn push_rejects_bad_capture_pattern() {
+        let mut demuxer = Demuxer::new();
+        let mut page = make_page(0, &[&id_header()]);
+        page[..4].copy_from_slice(b"Oggx");
+        assert!(matches!(
+            demuxer.push(&page),
+            Err(Error::Demuxer(DemuxerError::BadMagic))
+        ));
+    }
+
+    #[test]
+    fn push_parses_headers_and_queues_audio_packets() {
+        let mut demuxer = Demuxer::new();
+        let opus_packet = [0x00, 0xaa];
+        let page = make_page(0, &[&id_header(), &comment_header(), &opus_packet]);
+
+        demuxer.push(&page).unwrap();
+
+        assert!(demuxer.channels().is_some());
+        assert_eq!(demuxer.pre_skip(), 312);
+        assert!(demuxer.next_packet().unwrap().is_ok());
+        assert!(demuxer.next_packet().is_none());
+    }
+
+    #[test]
+    fn push_reassembles_a_packet_split_across_pushes() {
+        let mut demuxer = Demuxer::new();
+        let opus_packet = [0x00, 0xaa];
+        let page = make_page(0, &[&id_header(), &comment_header(), &opus_packet]);
+
+        // split the page in the middle of the identification header, well before it's complete
+        let (first, second) = page.split_at(10);
+        demuxer.push(first).unwrap();
+        assert!(demuxer.channels().is_none());
+
+        demuxer.push(second).unwrap();
+        assert!(demuxer.channels().is_some());
+        assert!(demuxer.next_packet().unwrap().is_ok());
+    }
+
+    #[test]
+    fn next_frame_drains_a_multi_frame_packet_in_chronological_order() {
+        let mut demuxer = Demuxer::new();
+        // config 0 (mono), code 1 (two equal-size CBR frames) carrying frame bytes 0xAA then 0xBB
+        let opus_packet = [0x01, 0xAA, 0xBB];
+        let page = make_page(0, &[&id_header(), &comment_header(), &opus_packet]);
+        demuxer.push(&page).unwrap();
+
+        let expected: Vec<Frame> = OpusPacket::new(&opus_packet).unwrap().frames().collect();
+        assert_eq!(expected.len(), 2);
+
+        assert_eq!(demuxer.next_frame().unwrap().unwrap(), expected[0]);
+        assert_eq!(demuxer.next_frame().unwrap().unwrap(), expected[1]);
+        assert!(demuxer.next_frame().is_none());
+    }
+}
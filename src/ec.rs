@@ -1,4 +1,12 @@
 //! An entropy decoder based on range coding.
+//!
+//! `RangeDecoder::decode_raw`/`decode_raw_bits`/`tell`/`tell_frac`, and all of `RangeEncoder`,
+//! have no caller yet anywhere in this crate: nothing in the `silk` module reads raw side-info
+//! bits or needs bit-usage accounting, and nothing round-trips a packet back through an encoder.
+//! That's intentional staging for the CELT/raw-side-info decoding and encode-side work this crate
+//! doesn't implement yet (see the crate-level docs), not forgotten wiring—each is still exercised
+//! directly by this module's own tests against the reference decoder, and `#[allow(dead_code)]`
+//! rather than `#[cfg(test)]` keeps them real, callable code in every build in the meantime.
 
 // This is the part of this implementation most directly based on the reference implementation, and
 // is more or less a direct port. I couldn't easily access the papers this was based on, and I
@@ -8,6 +16,15 @@
 // Still, I'd appriciate it if somebody who better understood the theory behind this rewrote this
 // with a more ideomatic API.
 
+/// Returns `⌈log2(x+1)⌉`, the number of bits required to store `x`, as used throughout
+/// [RFC 6716 § 4.1] (`ec_ilog` in the reference implementation).
+///
+/// [RFC 6716 § 4.1]: https://tools.ietf.org/html/rfc6716#section-4.1
+#[allow(dead_code)]
+fn ilog(x: u32) -> u32 {
+    32 - x.leading_zeros()
+}
+
 /// An entropy decoder based on range coding.
 ///
 /// This is implemented as described in [RFC6716 § 4.1].
@@ -21,6 +38,15 @@ pub(crate) struct RangeDecoder<'a> {
     range: u32,
     renorm: bool,
     value: u32,
+    /// Bit window for `RangeDecoder::decode_raw_bits`, holding bits already pulled from the tail
+    /// of `data` that haven't been consumed yet.
+    #[allow(dead_code)]
+    window_raw: u32,
+    /// Number of valid bits currently held in `window_raw`.
+    #[allow(dead_code)]
+    nbits_raw: u8,
+    /// Total number of bits read from `data` so far, used by `RangeDecoder::tell`/`tell_frac`.
+    nbits_total: u32,
 }
 
 impl<'a> RangeDecoder<'a> {
@@ -34,6 +60,9 @@ impl<'a> RangeDecoder<'a> {
             range: 128,
             renorm: (b0 & 1) != 0,
             value: (127 - (b0 >> 1)).into(),
+            window_raw: 0,
+            nbits_raw: 0,
+            nbits_total: 9,
         };
         ec_dec.normalize();
         ec_dec
@@ -41,7 +70,7 @@ impl<'a> RangeDecoder<'a> {
 
     /// Common operations between `RangeDecoder::decode` and `RangeDecoder::decode_bin`.
     fn decode_inner(&self, ft: u16, dividend: u32) -> u16 {
-        use std::convert::TryFrom;
+        use core::convert::TryFrom;
 
         // Unfortunately there's no way to (without unsafe code) guarantee optimization based on
         // our knowledge that `dividend` can't be 0. Hopefully the optimizer notices :)
@@ -109,7 +138,7 @@ impl<'a> RangeDecoder<'a> {
 
     pub(crate) fn decode_icdf(&mut self, icdf: &[u8], ftb: u8) -> Option<usize> {
         self.range.checked_shr(ftb.into()).and_then(|rshrftb| {
-            use std::mem::replace;
+            use core::mem::replace;
 
             let mut old_range;
 
@@ -134,12 +163,60 @@ impl<'a> RangeDecoder<'a> {
         })
     }
 
-    pub(crate) fn decode_raw(&mut self, _ft: u32) -> u32 {
-        unimplemented!()
+    /// Decodes a uniformly-distributed integer in the range `[0, ft)`, per [RFC 6716 § 4.1.5].
+    ///
+    /// Unlike `RangeDecoder::decode`/`RangeDecoder::update`, these bits are read from the *tail*
+    /// of the packet, back-to-front, via `RangeDecoder::decode_raw_bits`.
+    ///
+    /// [RFC 6716 § 4.1.5]: https://tools.ietf.org/html/rfc6716#section-4.1.5
+    #[allow(dead_code)]
+    pub(crate) fn decode_raw(&mut self, ft: u32) -> u32 {
+        let ftm1 = ft - 1;
+        let mut ftb = ilog(ftm1);
+
+        if ftb > 8 {
+            ftb -= 8;
+            let ft_hi = (ftm1 >> ftb) + 1;
+            let s = self.decode(ft_hi as u16).unwrap();
+            self.update(s, s + 1, ft_hi as u16);
+
+            let t = (u32::from(s) << ftb) | self.decode_raw_bits(ftb as u8);
+            t.min(ftm1)
+        } else {
+            let s = self.decode(ft as u16).unwrap();
+            self.update(s, s + 1, ft as u16);
+            u32::from(s)
+        }
     }
 
-    pub(crate) fn decode_raw_bits(&mut self, _bits: u8) -> u32 {
-        unimplemented!()
+    /// Decodes `bits` raw, uniformly-distributed bits, per [RFC 6716 § 4.1.4].
+    ///
+    /// These bits are read from the *tail* of the packet, back-to-front, through a little bit
+    /// window (`window_raw`/`nbits_raw`), independently of the ordinary range-coded symbols read from
+    /// the front (`index_ec`).
+    ///
+    /// [RFC 6716 § 4.1.4]: https://tools.ietf.org/html/rfc6716#section-4.1.4
+    #[allow(dead_code)]
+    pub(crate) fn decode_raw_bits(&mut self, bits: u8) -> u32 {
+        while self.nbits_raw < bits {
+            let byte = self
+                .data
+                .len()
+                .checked_sub(1 + self.index_raw)
+                .and_then(|i| self.data.get(i))
+                .copied()
+                .unwrap_or(0);
+            self.index_raw += 1;
+            self.nbits_total += 8;
+
+            self.window_raw |= u32::from(byte) << self.nbits_raw;
+            self.nbits_raw += 8;
+        }
+
+        let ret = self.window_raw & ((1 << bits) - 1);
+        self.window_raw >>= bits;
+        self.nbits_raw -= bits;
+        ret
     }
 
     /// Renormalizes `value` and `range` such that `range` lies entirely in the high-order symbol.
@@ -154,6 +231,7 @@ impl<'a> RangeDecoder<'a> {
 
             // update decoder state
             self.index_ec += 1;
+            self.nbits_total += 8;
             self.range <<= 8;
             self.renorm = (bn & 1) != 0;
             // This second subtraction may be replaced by (255 & !u32::from(sym)), which might(?)
@@ -183,11 +261,308 @@ impl<'a> RangeDecoder<'a> {
 
         self.normalize(); // renormalize the decoder state
     }
+
+    /// Returns the number of bits "used" so far, rounded up, per [RFC 6716 § 4.1.6.1].
+    ///
+    /// [RFC 6716 § 4.1.6.1]: https://tools.ietf.org/html/rfc6716#section-4.1.6.1
+    #[allow(dead_code)]
+    pub(crate) fn tell(&self) -> i32 {
+        self.nbits_total as i32 - ilog(self.range) as i32
+    }
+
+    /// Returns the number of bits "used" so far, in units of 1/8 bit, per [RFC 6716 § 4.1.6.2].
+    ///
+    /// [RFC 6716 § 4.1.6.2]: https://tools.ietf.org/html/rfc6716#section-4.1.6.2
+    #[allow(dead_code)]
+    pub(crate) fn tell_frac(&self) -> u32 {
+        let l = ilog(self.range);
+        let mut r = self.range >> (l - 16);
+        let mut l = l << 3;
+
+        for _ in 0..3 {
+            r = (r * r) >> 15;
+            let b = r >> 16;
+            l = (l << 1) | b;
+            r >>= b;
+        }
+
+        (self.nbits_total << 3) - l
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
+
+/// An entropy encoder based on range coding; the inverse of [`RangeDecoder`].
+///
+/// This is implemented as described in [RFC6716 § 4.1] and § 4.3, and is structured to mirror
+/// `RangeDecoder` symbol-for-symbol: `RangeEncoder::encode`/`encode_bin`/`encode_bit_logp`/
+/// `encode_icdf` are the exact inverses of the decoder methods of the same names (minus the
+/// `decode_*` prefix), and `RangeEncoder::enc_bits`/`enc_uint` are the inverses of
+/// `RangeDecoder::decode_raw_bits`/`decode_raw`.
+///
+/// Requires `alloc` (or `std`), since the encoded output is built up a byte at a time.
+///
+/// [`RangeDecoder`]: struct.RangeDecoder.html
+/// [RFC6716 § 4.1]: https://tools.ietf.org/html/rfc6716#section-4.1
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub(crate) struct RangeEncoder {
+    /// Finalized front-of-packet bytes.
+    buf: Vec<u8>,
+    /// Finalized back-of-packet (raw bit) bytes, in the order they were written; reversed into
+    /// place behind `buf` by `RangeEncoder::done`.
+    tail: Vec<u8>,
+    range: u32,
+    value: u32,
+    /// The byte awaiting carry propagation, or `-1` if none has been buffered yet.
+    rem: i32,
+    /// The number of pending `0xFF` bytes whose carry status isn't yet known.
+    ext: u32,
+    nbits_total: u32,
+    window_raw: u32,
+    nbits_raw: u8,
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[allow(dead_code)]
+impl RangeEncoder {
+    /// Top of the encoder's `value` register.
+    const VALUE_TOP: u32 = 1 << 31;
+
+    /// Threshold below which `range` must be renormalized.
+    const RANGE_MIN: u32 = 1 << 23;
+
+    /// Shift by which a finalized byte is extracted from `value`.
+    const VALUE_SHIFT: u32 = 23;
+
+    /// Returns a new range encoder with an empty output buffer.
+    pub(crate) fn new() -> RangeEncoder {
+        RangeEncoder {
+            buf: Vec::new(),
+            tail: Vec::new(),
+            range: RangeEncoder::VALUE_TOP,
+            value: 0,
+            rem: -1,
+            ext: 0,
+            // So that `tell()` reads 1 before anything has been encoded (33 minus the initial
+            // `ilog(range)` of 32), the same as a fresh `RangeDecoder`.
+            nbits_total: 33,
+            window_raw: 0,
+            nbits_raw: 0,
+        }
+    }
+
+    /// Buffers a finalized byte, propagating any pending carry into bytes already buffered.
+    fn carry_out(&mut self, sym: u32) {
+        if sym != 0xff {
+            let carry = sym >> 8;
+            if self.rem >= 0 {
+                self.buf.push((self.rem as u32 + carry) as u8);
+            }
+            if self.ext > 0 {
+                let sym = ((0xffu32 + carry) & 0xff) as u8;
+                self.buf.extend(core::iter::repeat(sym).take(self.ext as usize));
+                self.ext = 0;
+            }
+            self.rem = (sym & 0xff) as i32;
+        } else {
+            self.ext += 1;
+        }
+    }
+
+    /// Renormalizes `value` and `range`, buffering any newly-finalized bytes.
+    fn normalize(&mut self) {
+        while self.range <= RangeEncoder::RANGE_MIN {
+            let sym = self.value >> RangeEncoder::VALUE_SHIFT;
+            self.carry_out(sym);
+
+            self.value = (self.value << 8) & (RangeEncoder::VALUE_TOP - 1);
+            self.range <<= 8;
+            self.nbits_total += 8;
+        }
+    }
+
+    /// Encodes `fs`, a value lying within the range of some symbol in the current context, as
+    /// returned by the decoded symbol's `[fl, fh)` within `[0, ft)`.
+    ///
+    /// This is the inverse of `RangeDecoder::decode` followed by `RangeDecoder::update`.
+    pub(crate) fn encode(&mut self, fl: u16, fh: u16, ft: u16) {
+        let dividend = self.range / u32::from(ft);
+
+        if fl > 0 {
+            self.value += self.range - dividend * u32::from(ft - fl);
+            self.range = dividend * u32::from(fh - fl);
+        } else {
+            self.range -= dividend * u32::from(ft - fh);
+        }
+
+        self.normalize();
+    }
+
+    /// Identical to `RangeEncoder::encode` where `ft = (1 << ftb)`, avoiding a division.
+    ///
+    /// This is the inverse of `RangeDecoder::decode_bin` followed by `RangeDecoder::update`.
+    pub(crate) fn encode_bin(&mut self, fl: u16, fh: u16, ftb: u8) {
+        let ft = 1u16 << ftb;
+        let dividend = self.range >> ftb;
+
+        if fl > 0 {
+            self.value += self.range - dividend * u32::from(ft - fl);
+            self.range = dividend * u32::from(fh - fl);
+        } else {
+            self.range -= dividend * u32::from(ft - fh);
+        }
+
+        self.normalize();
+    }
+
+    /// Encodes a single binary symbol.
+    ///
+    /// This is the inverse of `RangeDecoder::decode_bit_logp`.
+    pub(crate) fn encode_bit_logp(&mut self, val: bool, logp: u8) {
+        let rshrlp = self.range >> logp;
+
+        if val {
+            self.value += self.range - rshrlp;
+            self.range = rshrlp;
+        } else {
+            self.range -= rshrlp;
+        }
+
+        self.normalize();
+    }
+
+    /// Encodes the symbol `s` under the inverse CDF `icdf`.
+    ///
+    /// This is the inverse of `RangeDecoder::decode_icdf`.
+    pub(crate) fn encode_icdf(&mut self, s: usize, icdf: &[u8], ftb: u8) {
+        let ft = 1u16 << ftb;
+        let fl = if s == 0 {
+            0
+        } else {
+            ft - u16::from(icdf[s - 1])
+        };
+        let fh = ft - u16::from(icdf[s]);
+
+        self.encode_bin(fl, fh, ftb);
+    }
+
+    /// Writes `bits` raw bits to the tail of the packet.
+    ///
+    /// This is the inverse of `RangeDecoder::decode_raw_bits`.
+    pub(crate) fn enc_bits(&mut self, val: u32, bits: u8) {
+        self.window_raw |= val << self.nbits_raw;
+        self.nbits_raw += bits;
+
+        while self.nbits_raw >= 8 {
+            self.tail.push((self.window_raw & 0xff) as u8);
+            self.window_raw >>= 8;
+            self.nbits_raw -= 8;
+        }
+
+        self.nbits_total += u32::from(bits);
+    }
+
+    /// Writes a uniformly-distributed integer in the range `[0, ft)` to the tail of the packet.
+    ///
+    /// This is the inverse of `RangeDecoder::decode_raw`.
+    pub(crate) fn enc_uint(&mut self, val: u32, ft: u32) {
+        let ftm1 = ft - 1;
+        let mut ftb = ilog(ftm1);
+
+        if ftb > 8 {
+            ftb -= 8;
+            let ft_hi = (ftm1 >> ftb) + 1;
+            let s = (val >> ftb) as u16;
+            self.encode(s, s + 1, ft_hi as u16);
+            self.enc_bits(val & ((1 << ftb) - 1), ftb as u8);
+        } else {
+            self.encode(val as u16, val as u16 + 1, ft as u16);
+        }
+    }
+
+    /// Returns the number of bits written so far, rounded up, per [RFC 6716 § 4.1.6.1].
+    ///
+    /// [RFC 6716 § 4.1.6.1]: https://tools.ietf.org/html/rfc6716#section-4.1.6.1
+    pub(crate) fn tell(&self) -> i32 {
+        self.nbits_total as i32 - ilog(self.range) as i32
+    }
+
+    /// Returns the number of bits written so far, in units of 1/8 bit, per [RFC 6716 § 4.1.6.2].
+    ///
+    /// [RFC 6716 § 4.1.6.2]: https://tools.ietf.org/html/rfc6716#section-4.1.6.2
+    pub(crate) fn tell_frac(&self) -> u32 {
+        let l = ilog(self.range);
+        let mut r = self.range >> (l - 16);
+        let mut l = l << 3;
+
+        for _ in 0..3 {
+            r = (r * r) >> 15;
+            let b = r >> 16;
+            l = (l << 1) | b;
+            r >>= b;
+        }
+
+        (self.nbits_total << 3) - l
+    }
+
+    /// Overwrites the first `nbits` (of at most 8) bits of the output with `val`, shifted into
+    /// the high bits of that first byte.
+    ///
+    /// Used to patch flags (such as a VBR indicator) into the stream after encoding has already
+    /// begun, once their final value is known.
+    pub(crate) fn patch_initial_bits(&mut self, val: u8, nbits: u8) {
+        let shift = 8 - nbits;
+        let mask = ((1u32 << nbits) - 1) << shift;
+
+        if let Some(first) = self.buf.first_mut() {
+            *first = (*first & !(mask as u8)) | (val << shift);
+        } else if self.rem >= 0 {
+            self.rem = (self.rem & !(mask as i32)) | i32::from(val << shift);
+        } else {
+            self.value = (self.value & !(mask << RangeEncoder::VALUE_SHIFT))
+                | (u32::from(val) << (RangeEncoder::VALUE_SHIFT + u32::from(shift)));
+        }
+    }
+
+    /// Finalizes the encoder, flushing all buffered state, and returns the encoded packet.
+    pub(crate) fn done(mut self) -> Vec<u8> {
+        let mut l = 32 - ilog(self.range);
+        let mut msk = (RangeEncoder::VALUE_TOP - 1) >> l;
+        let mut end = (self.value.wrapping_add(msk)) & !msk;
+
+        if (end | msk) >= self.value.wrapping_add(self.range) {
+            l += 1;
+            msk >>= 1;
+            end = (self.value.wrapping_add(msk)) & !msk;
+        }
+
+        while l > 0 {
+            let sym = end >> RangeEncoder::VALUE_SHIFT;
+            self.carry_out(sym);
+            end = (end << 8) & (RangeEncoder::VALUE_TOP - 1);
+            l = l.saturating_sub(8);
+        }
+
+        if self.rem >= 0 || self.ext > 0 {
+            self.carry_out(0);
+        }
+
+        if self.nbits_raw > 0 {
+            self.tail.push((self.window_raw & 0xff) as u8);
+        }
+
+        self.tail.reverse();
+        self.buf.extend(self.tail);
+        self.buf
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::RangeDecoder;
+    use super::{RangeDecoder, RangeEncoder};
     use opus_rfc8251_sys::ec_dec;
     use rand::Rng;
     use std::{
@@ -221,8 +596,9 @@ mod tests {
         }
 
         fn tell(&self) -> i32 {
-            // FIXME this should be using ec_ilog
-            self.ec_dec.nbits_total - (f64::from(self.ec_dec.rng).log10() as i32)
+            use opus_rfc8251_sys::ec_ilog;
+
+            self.ec_dec.nbits_total - unsafe { ec_ilog(self.ec_dec.rng) } as i32
         }
 
         fn tell_frac(&mut self) -> u32 {
@@ -682,6 +1058,142 @@ mod tests {
         }
     }
 
+    fn decode_raw_bits_generic<T>(buf: &[u8], mut get_bits: T)
+    where
+        T: FnMut(u8) -> u8,
+    {
+        // initialize decoders
+        let mut ref_dec = EcDec::new(&buf);
+        let mut opi_dec = RangeDecoder::new(&buf);
+
+        for i in 0..ITERATIONS as u8 {
+            let bits = get_bits(i);
+
+            let ref_res = ref_dec.decode_raw_bits(bits);
+            let opi_res = opi_dec.decode_raw_bits(bits);
+
+            assert_eq!(ref_res, opi_res); // test decodes
+        }
+    }
+
+    #[test]
+    fn decode_raw_bits_random_bits_randomly() {
+        use rand::distributions::Uniform;
+
+        let mut rng = rand::thread_rng();
+        let bits_dist = Uniform::new(0, 25);
+        for _ in 0..ITERATIONS {
+            let buf = rng.gen::<[u8; BUFFER_LEN]>();
+            decode_raw_bits_generic(&buf, |_| rng.sample(bits_dist));
+        }
+    }
+
+    #[test]
+    fn decode_raw_bits_random_bits_iteratively() {
+        for _ in 0..ITERATIONS {
+            let buf = rand::random::<[u8; BUFFER_LEN]>();
+            decode_raw_bits_generic(&buf, |i| i % 25);
+        }
+    }
+
+    #[test]
+    fn decode_raw_bits_empty_input() {
+        decode_raw_bits_generic(&[], |i| i % 25);
+    }
+
+    fn decode_raw_generic<T>(buf: &[u8], mut get_ft: T)
+    where
+        T: FnMut(u8) -> u32,
+    {
+        // initialize decoders
+        let mut ref_dec = EcDec::new(&buf);
+        let mut opi_dec = RangeDecoder::new(&buf);
+
+        for i in 0..ITERATIONS as u8 {
+            let ft = get_ft(i);
+
+            let ref_res = ref_dec.decode_raw(ft);
+            let opi_res = opi_dec.decode_raw(ft);
+
+            assert_eq!(ref_res, opi_res); // test decodes
+        }
+    }
+
+    #[test]
+    fn decode_raw_random_fts_randomly() {
+        use rand::distributions::Uniform;
+
+        let mut rng = rand::thread_rng();
+        let ft_dist = Uniform::new(1, u32::max_value());
+        for _ in 0..ITERATIONS {
+            let buf = rng.gen::<[u8; BUFFER_LEN]>();
+            decode_raw_generic(&buf, |_| rng.sample(ft_dist));
+        }
+    }
+
+    #[test]
+    fn decode_raw_random_fts_iteratively() {
+        for _ in 0..ITERATIONS {
+            let buf = rand::random::<[u8; BUFFER_LEN]>();
+            decode_raw_generic(&buf, |i| u32::from(i) + 1);
+        }
+    }
+
+    #[test]
+    fn decode_raw_empty_input() {
+        decode_raw_generic(&[], |i| u32::from(i) + 1);
+    }
+
+    fn tell_generic<T>(buf: &[u8], mut get_ft: T)
+    where
+        T: FnMut(u16) -> u16,
+    {
+        // initialize decoders
+        let mut ref_dec = EcDec::new(&buf);
+        let mut opi_dec = RangeDecoder::new(&buf);
+
+        assert_eq!(ref_dec.tell(), opi_dec.tell());
+
+        for i in 0..ITERATIONS as u16 {
+            let ft = get_ft(i);
+
+            let ref_res = ref_dec.decode(ft);
+            let opi_res = opi_dec.decode(ft).unwrap();
+            assert_eq!(ref_res, opi_res);
+
+            ref_dec.update(ref_res, ref_res + 1, ft);
+            opi_dec.update(opi_res, opi_res + 1, ft);
+
+            assert_eq!(ref_dec.tell(), opi_dec.tell());
+            assert_eq!(ref_dec.tell_frac(), opi_dec.tell_frac());
+        }
+    }
+
+    #[test]
+    fn tell_random_bytes_randomly() {
+        use rand::distributions::Uniform;
+
+        let mut rng = rand::thread_rng();
+        let ft_dist = Uniform::new(1, u16::max_value());
+        for _ in 0..ITERATIONS {
+            let buf = rng.gen::<[u8; BUFFER_LEN]>();
+            tell_generic(&buf, |_| rng.sample(ft_dist));
+        }
+    }
+
+    #[test]
+    fn tell_iterative_bytes_iteratively() {
+        for _ in 0..ITERATIONS {
+            let buf = rand::random::<[u8; BUFFER_LEN]>();
+            tell_generic(&buf, |i| i + 1);
+        }
+    }
+
+    #[test]
+    fn tell_empty_input() {
+        tell_generic(&[], |i| i + 1);
+    }
+
     #[test]
     fn test_send() {
         fn assert_send<T: Send>() {}
@@ -693,4 +1205,199 @@ mod tests {
         fn assert_sync<T: Sync>() {}
         assert_sync::<RangeDecoder<'_>>();
     }
+
+    struct EcEnc<'a> {
+        ec_enc: opus_rfc8251_sys::ec_enc,
+        _marker: PhantomData<&'a mut [u8]>,
+    }
+
+    impl<'a> EcEnc<'a> {
+        fn new(buf: &'a mut [u8]) -> EcEnc<'a> {
+            use opus_rfc8251_sys::ec_enc_init;
+            use std::mem::MaybeUninit;
+
+            let mut ec_enc = MaybeUninit::uninit();
+            unsafe { ec_enc_init(ec_enc.as_mut_ptr(), buf.as_mut_ptr(), buf.len() as _) };
+
+            EcEnc {
+                ec_enc: unsafe { ec_enc.assume_init() },
+                _marker: PhantomData,
+            }
+        }
+
+        fn encode(&mut self, fl: u16, fh: u16, ft: u16) {
+            use opus_rfc8251_sys::ec_encode;
+
+            unsafe { ec_encode(&mut self.ec_enc, fl.into(), fh.into(), ft.into()) }
+        }
+
+        fn encode_bin(&mut self, fl: u16, fh: u16, ftb: u8) {
+            use opus_rfc8251_sys::ec_encode_bin;
+
+            unsafe { ec_encode_bin(&mut self.ec_enc, fl.into(), fh.into(), ftb.into()) }
+        }
+
+        fn encode_bit_logp(&mut self, val: bool, logp: u8) {
+            use opus_rfc8251_sys::ec_enc_bit_logp;
+
+            unsafe { ec_enc_bit_logp(&mut self.ec_enc, val.into(), logp.into()) }
+        }
+
+        fn done(mut self) -> i32 {
+            use opus_rfc8251_sys::ec_enc_done;
+
+            unsafe { ec_enc_done(&mut self.ec_enc) };
+            self.ec_enc.error
+        }
+    }
+
+    fn encode_decode_roundtrip_generic<T>(mut get_ft: T)
+    where
+        T: FnMut(u16) -> u16,
+    {
+        let mut enc = RangeEncoder::new();
+        let fs: Vec<_> = (0..ITERATIONS as u16)
+            .map(|i| {
+                let ft = get_ft(i);
+                let fs = i % ft;
+                enc.encode(fs, fs + 1, ft);
+                (fs, ft)
+            })
+            .collect();
+
+        let buf = enc.done();
+        let mut dec = RangeDecoder::new(&buf);
+        for (fs, ft) in fs {
+            let res = dec.decode(ft).unwrap();
+            assert_eq!(res, fs);
+            dec.update(res, res + 1, ft);
+        }
+    }
+
+    #[test]
+    fn encode_decode_roundtrip_iteratively() {
+        encode_decode_roundtrip_generic(|i| i + 1);
+    }
+
+    #[test]
+    fn encode_decode_roundtrip_randomly() {
+        use rand::distributions::Uniform;
+
+        let mut rng = rand::thread_rng();
+        let ft_dist = Uniform::new(1, u16::max_value());
+        encode_decode_roundtrip_generic(|_| rng.sample(ft_dist));
+    }
+
+    #[test]
+    fn encode_bin_decode_bin_roundtrip() {
+        let mut enc = RangeEncoder::new();
+        let syms: Vec<_> = (0..ITERATIONS as u8)
+            .map(|i| {
+                let ftb = logp1gen(i);
+                let fs = u16::from(i) % (1 << ftb);
+                enc.encode_bin(fs, fs + 1, ftb);
+                (fs, ftb)
+            })
+            .collect();
+
+        let buf = enc.done();
+        let mut dec = RangeDecoder::new(&buf);
+        for (fs, ftb) in syms {
+            let res = dec.decode_bin(ftb).unwrap();
+            assert_eq!(res, fs);
+            dec.update(res, res + 1, 1 << ftb);
+        }
+    }
+
+    #[test]
+    fn encode_bit_logp_decode_bit_logp_roundtrip() {
+        let mut enc = RangeEncoder::new();
+        let bits: Vec<_> = (0..ITERATIONS as u8)
+            .map(|i| {
+                let logp = logp1gen(i);
+                let val = i % 2 == 0;
+                enc.encode_bit_logp(val, logp);
+                (val, logp)
+            })
+            .collect();
+
+        let buf = enc.done();
+        let mut dec = RangeDecoder::new(&buf);
+        for (val, logp) in bits {
+            assert_eq!(dec.decode_bit_logp(logp).unwrap(), val);
+        }
+    }
+
+    #[test]
+    fn encode_icdf_decode_icdf_roundtrip() {
+        let mut enc = RangeEncoder::new();
+        for _ in 0..ITERATIONS {
+            enc.encode_icdf(1, DUMMY_ICDF, 8);
+        }
+
+        let buf = enc.done();
+        let mut dec = RangeDecoder::new(&buf);
+        for _ in 0..ITERATIONS {
+            assert_eq!(dec.decode_icdf(DUMMY_ICDF, 8).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn enc_bits_decode_raw_bits_roundtrip() {
+        let mut enc = RangeEncoder::new();
+        let bits: Vec<_> = (0..ITERATIONS as u8)
+            .map(|i| {
+                let bits = i % 25;
+                let val = u32::from(i) & ((1 << bits) - 1);
+                enc.enc_bits(val, bits);
+                (val, bits)
+            })
+            .collect();
+
+        let buf = enc.done();
+        let mut dec = RangeDecoder::new(&buf);
+        for (val, bits) in bits {
+            assert_eq!(dec.decode_raw_bits(bits), val);
+        }
+    }
+
+    #[test]
+    fn enc_uint_decode_raw_roundtrip() {
+        let mut enc = RangeEncoder::new();
+        let vals: Vec<_> = (0..ITERATIONS as u8)
+            .map(|i| {
+                let ft = u32::from(i) + 1;
+                let val = u32::from(i) % ft;
+                enc.enc_uint(val, ft);
+                (val, ft)
+            })
+            .collect();
+
+        let buf = enc.done();
+        let mut dec = RangeDecoder::new(&buf);
+        for (val, ft) in vals {
+            assert_eq!(dec.decode_raw(ft), val);
+        }
+    }
+
+    #[test]
+    fn encode_against_reference() {
+        let mut ref_buf = [0; BUFFER_LEN];
+        let mut ref_enc = EcEnc::new(&mut ref_buf);
+        let mut opi_enc = RangeEncoder::new();
+
+        for i in 0..ITERATIONS as u16 {
+            let ft = i + 1;
+            let fs = i % ft;
+
+            ref_enc.encode(fs, fs + 1, ft);
+            opi_enc.encode(fs, fs + 1, ft);
+        }
+
+        let ref_offs = ref_enc.ec_enc.offs as usize;
+        assert_eq!(ref_enc.done(), 0);
+
+        let opi_buf = opi_enc.done();
+        assert_eq!(&ref_buf[..ref_offs], &opi_buf[..ref_offs]);
+    }
 }
@@ -2,52 +2,105 @@ use crate::{
     channel::ChannelLayoutError, packet::MalformedPacketError, silk::SilkError,
     slice_ext::BoundsError,
 };
-use std::{
-    error,
+
+#[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+use crate::demuxer::DemuxerError;
+#[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+use crate::io::ReadError;
+#[cfg(feature = "rtp")]
+use crate::rtp::RtpError;
+use core::{
     fmt::{self, Display, Formatter},
     result,
 };
 
+#[cfg(feature = "std")]
+use std::error;
+
+#[cfg(feature = "std")]
+use std::collections::TryReserveError;
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::collections::TryReserveError;
+
 #[cfg(feature = "ogg")]
 use ogg::OggReadError;
 
 #[cfg(feature = "ogg")]
 use crate::ogg::OggOpusError;
 
-#[derive(Debug)]
-#[cfg_attr(not(feature = "ogg"), derive(Clone, Copy))]
+#[cfg(all(feature = "webm", feature = "ogg"))]
+use crate::webm::WebMError;
+
+#[derive(Debug, Clone)]
 #[allow(variant_size_differences)]
 /// An error that has occured during decoding.
 pub enum Error {
     UnexpectedEof,
+    /// The packet called for a decode operation this decoder doesn't implement yet.
+    Unsupported,
+    /// A decode was asked to write more samples than an output slice had room for.
+    BufferTooSmall,
     /// A received packet was malformed.
     MalformedPacket(MalformedPacketError),
     /// The specified channel layout or mapping is malformed, unsupported, or otherwise invalid.
     ChannelLayout(ChannelLayoutError),
     Silk(SilkError),
+    /// An allocation needed to hold decoded samples failed, rather than aborting or unwinding.
+    AllocationFailed(TryReserveError),
     #[cfg(feature = "ogg")]
     /// The Ogg container itself could not be read.
     Ogg(OggReadError),
     #[cfg(feature = "ogg")]
     /// The Opus stream within the Ogg container could not be read.
     OggOpus(OggOpusError),
+    #[cfg(all(feature = "webm", feature = "ogg"))]
+    /// The WebM container, or the Opus track within it, could not be read.
+    WebM(WebMError),
+    #[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+    /// The container bytes pushed into a [`Demuxer`] were malformed.
+    ///
+    /// [`Demuxer`]: ../demuxer/struct.Demuxer.html
+    Demuxer(DemuxerError),
+    #[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+    /// The source underlying a [`demuxer::Reader`] could not be read.
+    ///
+    /// [`demuxer::Reader`]: ../demuxer/struct.Reader.html
+    Io(ReadError),
+    #[cfg(feature = "rtp")]
+    /// An RTP packet passed to [`rtp::Depayloader::push`] was malformed.
+    ///
+    /// [`rtp::Depayloader::push`]: ../rtp/struct.Depayloader.html#method.push
+    Rtp(RtpError),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Error::UnexpectedEof => f.write_str("unexpected end of stream"),
+            Error::Unsupported => f.write_str("decode operation not yet supported"),
+            Error::BufferTooSmall => f.write_str("output buffer too small for decoded samples"),
             Error::MalformedPacket(err) => err.fmt(f),
             Error::ChannelLayout(err) => err.fmt(f),
             Error::Silk(err) => err.fmt(f),
+            Error::AllocationFailed(err) => err.fmt(f),
             #[cfg(feature = "ogg")]
             Error::Ogg(err) => err.fmt(f),
             #[cfg(feature = "ogg")]
             Error::OggOpus(err) => err.fmt(f),
+            #[cfg(all(feature = "webm", feature = "ogg"))]
+            Error::WebM(err) => err.fmt(f),
+            #[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+            Error::Demuxer(err) => err.fmt(f),
+            #[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+            Error::Io(err) => err.fmt(f),
+            #[cfg(feature = "rtp")]
+            Error::Rtp(err) => err.fmt(f),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for Error {
     fn description(&self) -> &str {
         "Opus decoding error"
@@ -56,13 +109,24 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::UnexpectedEof => None,
+            Error::Unsupported => None,
+            Error::BufferTooSmall => None,
             Error::MalformedPacket(err) => Some(err),
             Error::ChannelLayout(err) => Some(err),
             Error::Silk(err) => Some(err),
+            Error::AllocationFailed(err) => Some(err),
             #[cfg(feature = "ogg")]
             Error::Ogg(err) => Some(err),
             #[cfg(feature = "ogg")]
             Error::OggOpus(err) => Some(err),
+            #[cfg(all(feature = "webm", feature = "ogg"))]
+            Error::WebM(err) => Some(err),
+            #[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+            Error::Demuxer(err) => Some(err),
+            #[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "rtp")]
+            Error::Rtp(err) => Some(err),
         }
     }
 }
@@ -91,6 +155,12 @@ impl From<SilkError> for Error {
     }
 }
 
+impl From<TryReserveError> for Error {
+    fn from(from: TryReserveError) -> Error {
+        Error::AllocationFailed(from)
+    }
+}
+
 #[cfg(feature = "ogg")]
 impl From<OggReadError> for Error {
     fn from(from: OggReadError) -> Error {
@@ -105,6 +175,34 @@ impl From<OggOpusError> for Error {
     }
 }
 
+#[cfg(all(feature = "webm", feature = "ogg"))]
+impl From<WebMError> for Error {
+    fn from(from: WebMError) -> Error {
+        Error::WebM(from)
+    }
+}
+
+#[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+impl From<DemuxerError> for Error {
+    fn from(from: DemuxerError) -> Error {
+        Error::Demuxer(from)
+    }
+}
+
+#[cfg(all(feature = "ogg", any(feature = "std", feature = "alloc")))]
+impl From<ReadError> for Error {
+    fn from(from: ReadError) -> Error {
+        Error::Io(from)
+    }
+}
+
+#[cfg(feature = "rtp")]
+impl From<RtpError> for Error {
+    fn from(from: RtpError) -> Error {
+        Error::Rtp(from)
+    }
+}
+
 /// A specialized [`Result`] type for Opus decoding.
 ///
 /// [`Result`]: https://doc.rust-lang.org/stable/std/result/enum.Result.html
@@ -0,0 +1,92 @@
+//! A minimal, `no_std`-friendly stand-in for [`std::io::Read`].
+//!
+//! The container and streaming-decoder code only ever needs to fill a buffer or report that the
+//! source ran out early, so rather than pull in all of `std::io` for `no_std` builds, this module
+//! defines the small slice of it that's actually used and bridges it to the real thing when the
+//! `std` feature is enabled.
+//!
+//! [`std::io::Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+
+use core::fmt::{self, Display, Formatter};
+
+/// The error type returned by a [`Read`] implementation.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum ReadError {
+    /// The source was exhausted before the requested number of bytes could be read.
+    UnexpectedEof,
+    /// The source encountered an error other than running out of data.
+    Other,
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReadError::UnexpectedEof => "unexpected end of stream",
+            ReadError::Other => "i/o error",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReadError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ReadError {
+    fn from(err: std::io::Error) -> ReadError {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => ReadError::UnexpectedEof,
+            _ => ReadError::Other,
+        }
+    }
+}
+
+/// A source of bytes.
+///
+/// This is a `no_std`-compatible analogue of [`std::io::Read`]; under the `std` feature, it's
+/// implemented for every type that already implements [`std::io::Read`].
+///
+/// [`std::io::Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+pub trait Read {
+    /// Fills `buf` completely, or returns [`ReadError::UnexpectedEof`] if the source runs out
+    /// first.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ReadError>;
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Read for R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ReadError> {
+        std::io::Read::read_exact(self, buf).map_err(ReadError::from)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_exact_fills_buffer_from_a_std_io_read_source() {
+        let mut source: &[u8] = &[1, 2, 3, 4];
+        let mut buf = [0; 4];
+        Read::read_exact(&mut source, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn read_exact_reports_unexpected_eof_when_source_runs_out() {
+        let mut source: &[u8] = &[1, 2];
+        let mut buf = [0; 4];
+        assert_eq!(
+            Read::read_exact(&mut source, &mut buf),
+            Err(ReadError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn read_error_display_is_human_readable() {
+        assert_eq!(
+            ReadError::UnexpectedEof.to_string(),
+            "unexpected end of stream"
+        );
+        assert_eq!(ReadError::Other.to_string(), "i/o error");
+    }
+}
@@ -1,4 +1,31 @@
+//! An Opus decoder, covering Ogg ([`ogg`]), WebM ([`webm`]), RTP ([`rtp`]), and raw multistream
+//! ([`multipacket`], [`demuxer`]) encapsulations.
+//!
+//! # Current limitation: no real decode yet
+//!
+//! [`packet::Decoder::decode`]—the decoder every format front end above ultimately calls into—
+//! only implements packet-loss concealment so far. Passing it a real (non-lost) packet always
+//! returns [`Error::Unsupported`] once SILK decoding finishes running (SILK/CELT synthesis itself
+//! isn't implemented yet); only the `None`-packet concealment path produces real PCM output.
+//! Every consumer in this crate inherits that gap: [`OggOpusReader`], [`WebMOpusReader`],
+//! [`rtp::Depayloader`]'s output fed through [`stream::StreamingDecoder`], and
+//! [`multipacket::Decoder`] all decode real packets only as far as `Err(Error::Unsupported)`.
+//!
+//! [`ogg`]: ogg/index.html
+//! [`webm`]: webm/index.html
+//! [`rtp`]: rtp/index.html
+//! [`multipacket`]: multipacket/index.html
+//! [`demuxer`]: demuxer/index.html
+//! [`packet::Decoder::decode`]: packet/struct.Decoder.html#method.decode
+//! [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+//! [`OggOpusReader`]: ogg/struct.OggOpusReader.html
+//! [`WebMOpusReader`]: webm/struct.WebMOpusReader.html
+//! [`rtp::Depayloader`]: rtp/struct.Depayloader.html
+//! [`stream::StreamingDecoder`]: stream/struct.StreamingDecoder.html
+//! [`multipacket::Decoder`]: multipacket/struct.Decoder.html
+
 #![cfg_attr(not(test), forbid(unsafe_code))]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(
     future_incompatible,
     nonstandard_style,
@@ -15,14 +42,28 @@
     variant_size_differences
 )]
 
-mod celt;
+// `#[cfg(test)]` code is built alongside a `no_std` crate, but still needs `std` for the test
+// harness and the FFI cross-checks against the reference decoder.
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 mod ec;
 mod error;
 mod silk;
 mod slice_ext;
 
 pub mod channel;
+pub mod demuxer;
+pub mod io;
+pub mod multipacket;
 pub mod ogg;
 pub mod packet;
+pub mod rtp;
+pub mod sample;
+pub mod stream;
+pub mod webm;
 
 pub use self::error::Error;
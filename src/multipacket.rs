@@ -1,18 +1,25 @@
 use crate::{
-    channel::MappingTable,
+    channel::{ChannelLayoutError, ChannelMapping, MappingTable, RtpChannelLayout},
     error::Result,
     packet::{Decoder as PktDecoder, Packet},
     sample::{Sample, Samples},
 };
-use std::vec::IntoIter;
+#[cfg(feature = "std")]
+use std::vec::{IntoIter, Vec};
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{
+    vec,
+    vec::{IntoIter, Vec},
+};
 
 #[derive(Debug, Clone)]
-pub struct Multipacket<'a> {
-    packets: IntoIter<Packet<'a>>,
+pub struct Multipacket {
+    packets: IntoIter<Packet>,
 }
 
-impl<'a> Multipacket<'a> {
-    pub fn new<T>(data: &'a [u8], mapping_table: &T) -> Result<Multipacket<'a>>
+impl Multipacket {
+    pub fn new<T>(data: &[u8], mapping_table: &T) -> Result<Multipacket>
     where
         T: ?Sized + MappingTable,
     {
@@ -35,8 +42,8 @@ impl<'a> Multipacket<'a> {
     }
 }
 
-impl<'a> Iterator for Multipacket<'a> {
-    type Item = Packet<'a>;
+impl Iterator for Multipacket {
+    type Item = Packet;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.packets.next()
@@ -51,39 +58,217 @@ impl<'a> Iterator for Multipacket<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Decodes a multistream Opus packet sequence—one [`Packet`] per internal stream, per [RFC 7845 §
+/// 5.1.1]—into PCM samples, merging the streams' channels into final output channel order via a
+/// [`MappingTable`].
+///
+/// [`Packet`]: ../packet/struct.Packet.html
+/// [`MappingTable`]: ../channel/trait.MappingTable.html
+/// [RFC 7845 § 5.1.1]: https://tools.ietf.org/html/rfc7845#section-5.1.1
+#[derive(Debug, Clone)]
 pub struct Decoder {
-    decoder: PktDecoder,
+    /// The total number of output channels, after routing.
+    channels: u8,
+    /// The number of leading streams decoded in stereo; the rest are decoded in mono.
+    coupled: u8,
+    /// One decoder per internal stream, in stream order.
+    streams: Vec<PktDecoder>,
 }
 
 impl Decoder {
-    fn new(sample_rate: u32, channels: u8) -> Decoder {
+    /// Creates a decoder outputting PCM at `sample_rate`, with `channels` output channels, for a
+    /// multistream packet sequence whose internal streams are as described by `mapping_table`.
+    pub fn new<M>(sample_rate: u32, channels: u8, mapping_table: &M) -> Decoder
+    where
+        M: ?Sized + MappingTable,
+    {
+        let coupled = mapping_table.coupled();
+
         Decoder {
-            decoder: PktDecoder::new(sample_rate, channels),
+            channels,
+            coupled,
+            streams: (0..mapping_table.streams())
+                .map(|i| PktDecoder::new(sample_rate, if i < coupled { 2 } else { 1 }))
+                .collect(),
         }
     }
 
-    pub fn decode<'a, S, T>(
+    /// Decodes each stream of `multipacket` into its own internal channels, then routes those
+    /// channels through `mapping_table` into `buf`, appending interleaved PCM samples, and
+    /// returns the number of samples written.
+    ///
+    /// Passing `None` signals a lost packet, triggering packet-loss concealment on every internal
+    /// stream in place of a real decode.
+    pub fn decode<M, S, T>(
+        &mut self,
+        multipacket: Option<Multipacket>,
+        mapping_table: &M,
+        buf: &mut S,
+    ) -> Result<usize>
+    where
+        M: ?Sized + MappingTable,
+        S: Samples<T>,
+        T: Sample,
+    {
+        let idecs = self.decode_streams(multipacket)?;
+        let internal = self.deinterleave(&idecs);
+        let refs: Vec<&[f32]> = internal.iter().map(Vec::as_slice).collect();
+
+        let mut out: Vec<Vec<f32>> = vec![Vec::new(); usize::from(self.channels)];
+        mapping_table.route(&refs, &mut out)?;
+
+        write_interleaved(&out, buf)
+    }
+
+    /// Like [`Decoder::decode`], but downmixes `channels`' routed, speaker-ordered output to an
+    /// RTP-style mono/stereo `target` (per [`ChannelMapping::downmix`]) before writing it into
+    /// `buf`, rather than writing `channels`' own channel count.
+    ///
+    /// Returns [`ChannelLayoutError::BadChannelsForFamily`] if `channels` isn't a Vorbis-family
+    /// mapping, which has no fold-down coefficients defined.
+    ///
+    /// [`Decoder::decode`]: #method.decode
+    /// [`ChannelMapping::downmix`]: ../channel/enum.ChannelMapping.html#method.downmix
+    /// [`ChannelLayoutError::BadChannelsForFamily`]: ../channel/enum.ChannelLayoutError.html#variant.BadChannelsForFamily
+    pub fn decode_downmixed<S, T>(
         &mut self,
-        multipacket: Option<Multipacket<'a>>,
+        multipacket: Option<Multipacket>,
+        channels: &ChannelMapping,
+        target: RtpChannelLayout,
         buf: &mut S,
-    ) -> Result<()>
+    ) -> Result<usize>
     where
         S: Samples<T>,
         T: Sample,
     {
+        let idecs = self.decode_streams(multipacket)?;
+        let internal = self.deinterleave(&idecs);
+        let refs: Vec<&[f32]> = internal.iter().map(Vec::as_slice).collect();
+
+        let mut routed: Vec<Vec<f32>> = vec![Vec::new(); usize::from(self.channels)];
+        channels.mapping_table().route(&refs, &mut routed)?;
+        let routed_refs: Vec<&[f32]> = routed.iter().map(Vec::as_slice).collect();
+
+        let mut down: Vec<Vec<f32>> = vec![Vec::new(); usize::from(target as u8)];
+        channels.downmix(target, &routed_refs, &mut down)?;
+
+        write_interleaved(&down, buf)
+    }
+
+    /// Decodes each stream of `multipacket` into its own internal (decoder-output) channels.
+    ///
+    /// Passing `None` signals a lost packet, triggering packet-loss concealment on every internal
+    /// stream in place of a real decode.
+    fn decode_streams(&mut self, multipacket: Option<Multipacket>) -> Result<Vec<Vec<f32>>> {
         if let Some(multipacket) = multipacket {
-            let mut idecs: Vec<Vec<T>> = Vec::with_capacity(multipacket.size_hint().1.unwrap_or(0));
-            for packet in multipacket {
-                idecs.push(Vec::new());
-                self.decoder.decode(Some(packet), idecs.last_mut().unwrap());
+            let packets: Vec<Packet> = multipacket.collect();
+            if packets.len() != self.streams.len() {
+                return Err(ChannelLayoutError::StreamCountMismatch.into());
             }
 
-            // TODO merge idecs
-            unimplemented!()
+            self.streams
+                .iter_mut()
+                .zip(packets)
+                .map(|(decoder, packet): (&mut PktDecoder, Packet)| {
+                    let mut samples = Vec::new();
+                    decoder.decode(Some(packet), &mut samples)?;
+                    Ok(samples)
+                })
+                .collect::<Result<Vec<Vec<f32>>>>()
         } else {
-            // TODO packet loss concealment
-            unimplemented!()
+            self.streams
+                .iter_mut()
+                .map(|decoder| {
+                    let mut samples = Vec::new();
+                    decoder.decode(None, &mut samples)?;
+                    Ok(samples)
+                })
+                .collect::<Result<Vec<Vec<f32>>>>()
+        }
+    }
+
+    /// De-interleaves each stream's decoded samples into one slice per internal (decoder-output)
+    /// channel—two for a coupled (stereo) stream, one otherwise.
+    fn deinterleave(&self, idecs: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let mut internal: Vec<Vec<f32>> =
+            Vec::with_capacity(idecs.len() + usize::from(self.coupled));
+        for (i, samples) in idecs.iter().enumerate() {
+            if i < usize::from(self.coupled) {
+                let (left, right) = samples
+                    .chunks_exact(2)
+                    .map(|pair| (pair[0], pair[1]))
+                    .unzip();
+                internal.push(left);
+                internal.push(right);
+            } else {
+                internal.push(samples.clone());
+            }
         }
+        internal
+    }
+}
+
+/// Interleaves one sample buffer per output channel into `buf`, and returns the number of
+/// samples written.
+fn write_interleaved<S, T>(out: &[Vec<f32>], buf: &mut S) -> Result<usize>
+where
+    S: Samples<T>,
+    T: Sample,
+{
+    let samples_per_channel = out.first().map_or(0, Vec::len);
+    let needed = samples_per_channel.saturating_mul(out.len());
+    buf.try_reserve_samples(needed)?;
+
+    for t in 0..samples_per_channel {
+        for channel in out {
+            buf.push_sample(T::from_f32(channel[t]));
+        }
+    }
+
+    Ok(needed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        channel::{ChannelLayoutError, RtpChannelLayout, StandardMappingTable},
+        error::Error,
+    };
+
+    /// A minimal, internally-framed, single-frame Opus packet `Packet::new` accepts: TOC (config
+    /// 0, mono, code 0) followed by one byte of frame data.
+    const OPUS_PACKET: &[u8] = &[0x00, 0xaa];
+
+    #[test]
+    fn multipacket_new_parses_one_packet_per_stream() {
+        let layout = RtpChannelLayout::Mono;
+        let multipacket = Multipacket::new(OPUS_PACKET, &layout).unwrap();
+        assert_eq!(multipacket.count(), 1);
+    }
+
+    #[test]
+    fn decoder_new_creates_one_decoder_per_mapping_table_stream() {
+        let layout = RtpChannelLayout::Stereo;
+        let decoder = Decoder::new(48_000, 2, &layout);
+        assert_eq!(decoder.streams.len(), 1);
+        assert_eq!(decoder.coupled, 1);
+    }
+
+    #[test]
+    fn decode_rejects_a_multipacket_with_the_wrong_stream_count() {
+        // parsed against a single-stream mapping, so this multipacket holds exactly one packet
+        let multipacket = Multipacket::new(OPUS_PACKET, &RtpChannelLayout::Mono).unwrap();
+
+        // but the decoder was built for a two-stream mapping
+        let decode_table = StandardMappingTable::new(2, &[2, 0, 0, 1]).unwrap();
+        let mut decoder = Decoder::new(48_000, 2, &decode_table);
+
+        assert!(matches!(
+            decoder.decode(Some(multipacket), &decode_table, &mut Vec::<f32>::new()),
+            Err(Error::ChannelLayout(
+                ChannelLayoutError::StreamCountMismatch
+            ))
+        ));
     }
 }
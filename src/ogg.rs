@@ -1,18 +1,22 @@
 //! Decoding of Ogg-encapsulated Opus streams.
-#![cfg(feature = "ogg")]
+//!
+//! [`OggOpusReader`] reads from an owned, seekable `R: Read + Seek` and so requires `std`; for
+//! `no_std`, incremental, or zero-seeking use, see [`crate::demuxer::Demuxer`] instead.
+#![cfg(all(feature = "ogg", feature = "std"))]
 
 use crate::{
-    channel::ChannelMapping,
-    error::Result,
-    packet::{Frame, Multistream},
+    channel::{ChannelMapping, SpeakerPosition},
+    error::{Error, Result},
+    packet::{Decoder as PacketDecoder, Frame, Multistream, Packet as OpusPacket},
     slice_ext::SliceExt,
 };
-use ogg::PacketReader;
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
 use std::{
     error,
     fmt::{self, Debug, Display, Formatter},
-    io::prelude::*,
+    io::{prelude::*, SeekFrom},
     num::NonZeroU32,
+    time::Duration,
 };
 
 /// The error type returned when the Ogg Opus stream is malformed.
@@ -27,6 +31,21 @@ pub enum OggOpusError {
     /// The Identificaion Header indicated that this Ogg file conforms to an unsupported version of
     /// the specification.
     UnsupportedVersion,
+    /// A well-known comment tag had a value that didn't match its expected format.
+    MalformedComment,
+    /// The stream multiplexes more than one elementary Opus stream per packet, which
+    /// [`OggOpusReader::read_samples`] doesn't support yet.
+    ///
+    /// [`OggOpusReader::read_samples`]: struct.OggOpusReader.html#method.read_samples
+    MultistreamUnsupported,
+    /// The underlying stream doesn't support seeking, as [`OggOpusReader::seek`] requires.
+    ///
+    /// [`OggOpusReader::seek`]: struct.OggOpusReader.html#method.seek
+    NotSeekable,
+    /// [`OggOpusReader::seek`] was asked for a position past the end of the stream.
+    ///
+    /// [`OggOpusReader::seek`]: struct.OggOpusReader.html#method.seek
+    SeekPastEnd,
 }
 
 impl Display for OggOpusError {
@@ -36,6 +55,10 @@ impl Display for OggOpusError {
             OggOpusError::BadPaging => "bad ogg paging alignment",
             OggOpusError::BadMagic => "invalid magic number",
             OggOpusError::UnsupportedVersion => "unsupported encapsulation specification version",
+            OggOpusError::MalformedComment => "malformed comment tag value",
+            OggOpusError::MultistreamUnsupported => "multistream opus is not yet supported",
+            OggOpusError::NotSeekable => "stream is not seekable",
+            OggOpusError::SeekPastEnd => "seek target is past the end of the stream",
         })
     }
 }
@@ -43,7 +66,7 @@ impl Display for OggOpusError {
 impl error::Error for OggOpusError {}
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
-struct IdHeader {
+pub(crate) struct IdHeader {
     /// Encapsulation specification version.
     version: u8,
     /// Output channel configuration.
@@ -70,7 +93,7 @@ impl IdHeader {
     const VERSION_MINOR_MASK: u8 = 0b0000_1111;
 
     /// Create a new ID header representation from bytes.
-    fn new(data: &[u8]) -> Result<Self> {
+    pub(crate) fn new(data: &[u8]) -> Result<Self> {
         use byteorder::{ByteOrder, LE};
 
         if data.get_res(..8)? == Self::MAGIC {
@@ -86,7 +109,7 @@ impl IdHeader {
                     )?,
                     pre_skip: LE::read_u16(data.get_res(10..=11)?),
                     sample_rate: NonZeroU32::new(LE::read_u32(data.get_res(12..=15)?)),
-                    output_gain: LE::read_i16(data.get_res(15..=16)?),
+                    output_gain: LE::read_i16(data.get_res(16..=17)?),
                 })
             } else {
                 Err(OggOpusError::UnsupportedVersion.into())
@@ -97,7 +120,7 @@ impl IdHeader {
     }
 
     /// Returns the encapsulation specification version as (major, minor).
-    fn version(&self) -> (u8, u8) {
+    pub(crate) fn version(&self) -> (u8, u8) {
         const MAJOR_SHIFT_RIGHT: u32 = IdHeader::VERSION_MAJOR_MASK.trailing_zeros();
         (
             (self.version & IdHeader::VERSION_MAJOR_MASK) >> MAJOR_SHIFT_RIGHT,
@@ -106,25 +129,62 @@ impl IdHeader {
     }
 
     /// Returns the output channel configuration.
-    fn channels(&self) -> &ChannelMapping {
+    pub(crate) fn channels(&self) -> &ChannelMapping {
         &self.channels
     }
 
+    /// Returns the speaker position of each output channel, in channel order, or `None` for a
+    /// discrete-channel mapping, whose channel semantics the specification leaves
+    /// application-defined.
+    pub(crate) fn speaker_positions(&self) -> Option<Vec<SpeakerPosition>> {
+        self.channels.speaker_positions()
+    }
+
     /// Returns the number of samples (at 48 kHz) to discard when beginning playback.
-    fn pre_skip(&self) -> u16 {
+    pub(crate) fn pre_skip(&self) -> u16 {
         self.pre_skip
     }
 
     /// Returns the encoding sample rate.
-    fn sample_rate(&self) -> Option<NonZeroU32> {
+    pub(crate) fn sample_rate(&self) -> Option<NonZeroU32> {
         self.sample_rate
     }
 
     /// Returns 20*log_10 of the factor by which to scale the decoder output to
     /// receive the desired playback volume.
-    fn output_gain(&self) -> i16 {
+    pub(crate) fn output_gain(&self) -> i16 {
         self.output_gain
     }
+
+    /// Serializes this identification header back to its on-wire byte layout: the magic number,
+    /// version, channel count, pre-skip, input sample rate, output gain, and channel mapping
+    /// family/table, in the format parsed by [`IdHeader::new`].
+    ///
+    /// [`IdHeader::new`]: #method.new
+    pub(crate) fn write(&self, buf: &mut Vec<u8>) {
+        use byteorder::{ByteOrder, LE};
+
+        buf.extend_from_slice(&Self::MAGIC);
+        buf.push(self.version);
+        buf.push(self.channels.channels());
+
+        let mut pre_skip = [0; 2];
+        LE::write_u16(&mut pre_skip, self.pre_skip);
+        buf.extend_from_slice(&pre_skip);
+
+        let mut sample_rate = [0; 4];
+        LE::write_u32(
+            &mut sample_rate,
+            self.sample_rate.map_or(0, NonZeroU32::get),
+        );
+        buf.extend_from_slice(&sample_rate);
+
+        let mut output_gain = [0; 2];
+        LE::write_i16(&mut output_gain, self.output_gain);
+        buf.extend_from_slice(&output_gain);
+
+        self.channels.write(buf);
+    }
 }
 
 /// An iterator over user comments.
@@ -170,6 +230,167 @@ impl<'a> Iterator for Comments<'a> {
     }
 }
 
+/// Typed access to well-known [Vorbis comment] tags.
+///
+/// Tag names are matched case-insensitively, as recommended by the [Vorbis comment]
+/// specification.
+///
+/// [Vorbis comment]: https://www.xiph.org/vorbis/doc/v-comment.html
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Tags<'a> {
+    comments: Comments<'a>,
+}
+
+impl<'a> Tags<'a> {
+    fn find(&self, key: &str) -> Option<&'a str> {
+        self.comments
+            .clone()
+            .find(|&(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    fn gain(&self, key: &str) -> Option<Result<i16>> {
+        self.find(key).map(|value| {
+            value
+                .parse()
+                .map_err(|_| OggOpusError::MalformedComment.into())
+        })
+    }
+
+    /// Returns the track's artist, from the `ARTIST` tag.
+    pub fn artist(&self) -> Option<&'a str> {
+        self.find("ARTIST")
+    }
+
+    /// Returns the track's title, from the `TITLE` tag.
+    pub fn title(&self) -> Option<&'a str> {
+        self.find("TITLE")
+    }
+
+    /// Returns the base64-encoded cover art block, from the `METADATA_BLOCK_PICTURE` tag.
+    ///
+    /// See the [FLAC picture block] specification for the format of the decoded data.
+    ///
+    /// [FLAC picture block]: https://xiph.org/flac/format.html#metadata_block_picture
+    pub fn metadata_block_picture(&self) -> Option<&'a str> {
+        self.find("METADATA_BLOCK_PICTURE")
+    }
+
+    /// Returns the track gain, in Q7.8 fixed-point dB, from the `R128_TRACK_GAIN` tag.
+    ///
+    /// See [EBU R128] for the loudness measurement this is relative to.
+    ///
+    /// [EBU R128]: https://tech.ebu.ch/docs/r/r128.pdf
+    pub fn r128_track_gain(&self) -> Option<Result<i16>> {
+        self.gain("R128_TRACK_GAIN")
+    }
+
+    /// Returns the album gain, in Q7.8 fixed-point dB, from the `R128_ALBUM_GAIN` tag.
+    ///
+    /// See [EBU R128] for the loudness measurement this is relative to.
+    ///
+    /// [EBU R128]: https://tech.ebu.ch/docs/r/r128.pdf
+    pub fn r128_album_gain(&self) -> Option<Result<i16>> {
+        self.gain("R128_ALBUM_GAIN")
+    }
+
+    /// Combines `output_gain`—[`OggOpusReader::output_gain`]'s `OpusHead` field—with this
+    /// track's `R128_TRACK_GAIN` tag into a single effective dB scale factor, in the same Q7.8
+    /// fixed-point format as both, for loudness-normalized playback.
+    ///
+    /// Returns `None` if there is no `R128_TRACK_GAIN` tag.
+    ///
+    /// [`OggOpusReader::output_gain`]: struct.OggOpusReader.html#method.output_gain
+    pub fn normalization_gain(&self, output_gain: i16) -> Option<Result<i16>> {
+        self.r128_track_gain()
+            .map(|gain| gain.map(|gain| gain.saturating_add(output_gain)))
+    }
+
+    /// Returns an iterator over embedded cover art pictures, decoded from any
+    /// `METADATA_BLOCK_PICTURE` tags.
+    ///
+    /// Each item is the result of decoding one picture; a tag with bad base64 or non-UTF-8 text
+    /// yields [`OggOpusError::MalformedComment`], and one whose picture block is truncated yields
+    /// `Error::UnexpectedEof`, without stopping iteration over the rest.
+    ///
+    /// [`OggOpusError::MalformedComment`]: enum.OggOpusError.html#variant.MalformedComment
+    pub fn pictures(&self) -> impl Iterator<Item = Result<Picture>> + '_ {
+        self.comments
+            .clone()
+            .filter(|&(name, _)| name.eq_ignore_ascii_case("METADATA_BLOCK_PICTURE"))
+            .map(|(_, value)| Picture::decode(value))
+    }
+}
+
+/// A decoded cover art picture, from a `METADATA_BLOCK_PICTURE` comment tag.
+///
+/// See the [FLAC picture block] specification for the meaning of each field.
+///
+/// [FLAC picture block]: https://xiph.org/flac/format.html#metadata_block_picture
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Picture {
+    /// The picture type code, e.g. `3` for "Cover (front)".
+    pub picture_type: u32,
+    /// The MIME type of `data`.
+    pub mime_type: String,
+    /// A short description of the picture.
+    pub description: String,
+    /// The picture's width, in pixels.
+    pub width: u32,
+    /// The picture's height, in pixels.
+    pub height: u32,
+    /// The picture's color depth, in bits per pixel.
+    pub depth: u32,
+    /// The number of colors used, for indexed-color pictures, or `0` otherwise.
+    pub colors: u32,
+    /// The picture data itself, in the format given by `mime_type`.
+    pub data: Vec<u8>,
+}
+
+impl Picture {
+    /// Decodes a single picture from the base64-encoded value of a `METADATA_BLOCK_PICTURE` tag.
+    fn decode(value: &str) -> Result<Picture> {
+        use byteorder::{ByteOrder, BE};
+
+        let data = base64::decode(value).map_err(|_| OggOpusError::MalformedComment)?;
+        let data = &data[..];
+
+        let picture_type = BE::read_u32(data.get_res(0..4)?);
+
+        let mime_start = 8;
+        let mime_len = BE::read_u32(data.get_res(4..mime_start)?) as usize;
+        let mime_end = mime_start + mime_len;
+        let mime_type = String::from_utf8(data.get_res(mime_start..mime_end)?.to_owned())
+            .map_err(|_| OggOpusError::MalformedComment)?;
+
+        let desc_start = mime_end + 4;
+        let desc_len = BE::read_u32(data.get_res(mime_end..desc_start)?) as usize;
+        let desc_end = desc_start + desc_len;
+        let description = String::from_utf8(data.get_res(desc_start..desc_end)?.to_owned())
+            .map_err(|_| OggOpusError::MalformedComment)?;
+
+        let width = BE::read_u32(data.get_res(desc_end..desc_end + 4)?);
+        let height = BE::read_u32(data.get_res(desc_end + 4..desc_end + 8)?);
+        let depth = BE::read_u32(data.get_res(desc_end + 8..desc_end + 12)?);
+        let colors = BE::read_u32(data.get_res(desc_end + 12..desc_end + 16)?);
+
+        let data_start = desc_end + 20;
+        let data_len = BE::read_u32(data.get_res(desc_end + 16..data_start)?) as usize;
+        let picture_data = data.get_res(data_start..data_start + data_len)?.to_owned();
+
+        Ok(Picture {
+            picture_type,
+            mime_type,
+            description,
+            width,
+            height,
+            depth,
+            colors,
+            data: picture_data,
+        })
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Hash)]
 struct CommentHeader {
     comments: Box<[u8]>,
@@ -230,12 +451,54 @@ impl CommentHeader {
         }
     }
 
+    /// Returns typed access to well-known comment tags.
+    fn tags(&self) -> Tags<'_> {
+        Tags {
+            comments: self.comments(),
+        }
+    }
+
     /// Returns the vendor string.
     fn vendor(&self) -> &str {
         &self.vendor[..]
     }
 }
 
+/// Serializes a fresh `OpusTags` comment header packet from a vendor string and `(name, value)`
+/// comment pairs, in the format parsed by [`CommentHeader::new`].
+///
+/// [`CommentHeader::new`]: struct.CommentHeader.html#method.new
+fn write_comment_header<'a, I>(buf: &mut Vec<u8>, vendor: &str, comments: I)
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    use byteorder::{ByteOrder, LE};
+
+    buf.extend_from_slice(&CommentHeader::MAGIC);
+
+    let mut vendor_len = [0; 4];
+    LE::write_u32(&mut vendor_len, vendor.len() as u32);
+    buf.extend_from_slice(&vendor_len);
+    buf.extend_from_slice(vendor.as_bytes());
+
+    let count_pos = buf.len();
+    buf.extend_from_slice(&[0; 4]);
+
+    let mut count = 0u32;
+    for (name, value) in comments {
+        let comment = format!("{}={}", name, value);
+
+        let mut comment_len = [0; 4];
+        LE::write_u32(&mut comment_len, comment.len() as u32);
+        buf.extend_from_slice(&comment_len);
+        buf.extend_from_slice(comment.as_bytes());
+
+        count += 1;
+    }
+
+    LE::write_u32(&mut buf[count_pos..count_pos + 4], count);
+}
+
 impl Debug for CommentHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let mut ds = f.debug_struct("CommentHeader");
@@ -283,7 +546,7 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.frame_cache.is_empty() {
-            self.frame_cache = match self.reader.reader.read_packet() {
+            self.frame_cache = match self.reader.reader_mut().read_packet() {
                 Ok(Some(packet)) => match Multistream::new(
                     &packet.data[..],
                     self.reader.id_header.channels().mapping_table(),
@@ -300,9 +563,295 @@ where
     }
 }
 
+/// Sample rate, in Hz, that Opus granule positions (and therefore pre-skip and end-trim) are
+/// always expressed in, regardless of the original input or playback sample rate.
+const DECODE_SAMPLE_RATE: u32 = 48_000;
+
+/// Scales a decoded sample by the `OpusHead` output gain, saturating on overflow.
+///
+/// `output_gain` is 20&thinsp;log<sub>10</sub> of the desired scale factor, in Q7.8 fixed-point.
+fn apply_gain(sample: i16, output_gain: i16) -> i16 {
+    use core::i16::{MAX, MIN};
+
+    if output_gain == 0 {
+        return sample;
+    }
+
+    let factor = 10f32.powf(f32::from(output_gain) / (256.0 * 20.0));
+    let scaled = (f32::from(sample) * factor).round();
+    scaled.max(f32::from(MIN)).min(f32::from(MAX)) as i16
+}
+
+/// An iterator over decoded PCM samples, with the encoder's leading delay and trailing padding
+/// already discarded and the `OpusHead` output gain already applied.
+///
+/// Samples are interleaved by channel, e.g. `[left, right, left, right, ...]` for stereo.
+///
+/// Multistream Opus isn't supported yet; [`OggOpusReader::read_samples`] returns
+/// [`OggOpusError::MultistreamUnsupported`] up front for those streams.
+///
+/// [`OggOpusReader::read_samples`]: struct.OggOpusReader.html#method.read_samples
+/// [`OggOpusError::MultistreamUnsupported`]: enum.OggOpusError.html#variant.MultistreamUnsupported
+pub struct Samples<R: Read + Seek> {
+    reader: PacketReader<R>,
+    decoder: PacketDecoder,
+    output_gain: i16,
+    channels: u8,
+    /// The `pre-skip` field from the `OpusHead` packet.
+    pre_skip: u64,
+    /// Interleaved samples still to discard from the front of the decoded stream.
+    to_skip: u64,
+    /// The total number of interleaved samples the stream should end at, once known from the
+    /// final page's granule position.
+    end: Option<u64>,
+    /// Interleaved samples emitted so far (after pre-skip, before end-trim).
+    emitted: u64,
+    buf: Vec<i16>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R> Samples<R>
+where
+    R: Read + Seek,
+{
+    fn new(reader: PacketReader<R>, id_header: &IdHeader) -> Samples<R> {
+        let mapping_table = id_header.channels().mapping_table();
+        let channels =
+            mapping_table.coupled() * 2 + (mapping_table.streams() - mapping_table.coupled());
+        let pre_skip = u64::from(id_header.pre_skip());
+
+        Samples {
+            reader,
+            decoder: PacketDecoder::new(DECODE_SAMPLE_RATE, channels),
+            output_gain: id_header.output_gain(),
+            channels,
+            pre_skip,
+            to_skip: pre_skip * u64::from(channels),
+            end: None,
+            emitted: 0,
+            buf: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R> Iterator for Samples<R>
+where
+    R: Read + Seek,
+{
+    type Item = Result<i16>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(&sample) = self.buf.get(self.pos) {
+                self.pos += 1;
+
+                if self.to_skip > 0 {
+                    self.to_skip -= 1;
+                    continue;
+                }
+
+                if self.end.map_or(false, |end| self.emitted >= end) {
+                    self.done = true;
+                    return None;
+                }
+
+                self.emitted += 1;
+                return Some(Ok(apply_gain(sample, self.output_gain)));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            let raw_packet = match self.reader.read_packet() {
+                Ok(Some(raw_packet)) => raw_packet,
+                Ok(None) => {
+                    self.done = true;
+                    continue;
+                }
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            if raw_packet.last_in_stream() {
+                let total = raw_packet.absgp_page().saturating_sub(self.pre_skip);
+                self.end = Some(total.saturating_mul(u64::from(self.channels)));
+            }
+
+            let opus_packet = match OpusPacket::new(&raw_packet.data[..]) {
+                Ok(opus_packet) => opus_packet,
+                Err(err) => return Some(Err(err)),
+            };
+
+            self.buf.clear();
+            if let Err(err) = self.decoder.decode(Some(opus_packet), &mut self.buf) {
+                return Some(Err(err));
+            }
+            self.pos = 0;
+        }
+    }
+}
+
+impl<R> Debug for Samples<R>
+where
+    R: Read + Seek,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Samples")
+            .field("reader", &ElidedStruct("PacketReader"))
+            .field("decoder", &self.decoder)
+            .field("output_gain", &self.output_gain)
+            .field("channels", &self.channels)
+            .field("pre_skip", &self.pre_skip)
+            .field("to_skip", &self.to_skip)
+            .field("end", &self.end)
+            .field("emitted", &self.emitted)
+            .field("buf", &self.buf)
+            .field("pos", &self.pos)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// A helper that debug-prints as the given string, for eliding fields that don't implement
+/// [`Debug`] themselves.
+///
+/// [`Debug`]: https://doc.rust-lang.org/stable/std/fmt/trait.Debug.html
+#[derive(PartialEq, Eq, Clone, Copy, Hash)]
+pub(crate) struct ElidedStruct<'a>(&'a str);
+
+impl Debug for ElidedStruct<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad(self.0)
+    }
+}
+
+/// A target position for [`OggOpusReader::seek`].
+///
+/// [`OggOpusReader::seek`]: struct.OggOpusReader.html#method.seek
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum SeekTarget {
+    /// An exact sample offset, at 48 kHz, from the start of the decoded stream.
+    Sample(u64),
+    /// A point in time from the start of the decoded stream.
+    Time(Duration),
+}
+
+impl From<u64> for SeekTarget {
+    fn from(sample: u64) -> SeekTarget {
+        SeekTarget::Sample(sample)
+    }
+}
+
+impl From<Duration> for SeekTarget {
+    fn from(time: Duration) -> SeekTarget {
+        SeekTarget::Time(time)
+    }
+}
+
+impl SeekTarget {
+    /// Returns the target as a sample offset at 48 kHz.
+    fn as_samples(self) -> u64 {
+        match self {
+            SeekTarget::Sample(sample) => sample,
+            SeekTarget::Time(time) => {
+                (time.as_secs_f64() * f64::from(DECODE_SAMPLE_RATE)).round() as u64
+            }
+        }
+    }
+}
+
+/// The 4-byte pattern that begins every Ogg page.
+const CAPTURE_PATTERN: [u8; 4] = *b"OggS";
+
+/// Length, in bytes, of an Ogg page header up to (not including) its segment table.
+const PAGE_HEADER_LEN: u64 = 27;
+
+/// The location and granule position of an Ogg page found by [`scan_page`] or [`page_before`].
+///
+/// [`scan_page`]: fn.scan_page.html
+/// [`page_before`]: fn.page_before.html
+#[derive(Debug, Clone, Copy)]
+struct PageInfo {
+    /// Byte offset of the page's capture pattern.
+    start: u64,
+    /// Byte offset just past the end of the page, including its segment table and packet data.
+    end: u64,
+    /// The page's granule position, or `u64::max_value()` if no packet completes on this page.
+    granule: u64,
+}
+
+/// Scans forward from `from`, returning the location and granule position of the first Ogg page
+/// found, snapping to its capture pattern.
+fn scan_page<R>(reader: &mut R, from: u64) -> Result<PageInfo>
+where
+    R: Read + Seek,
+{
+    use byteorder::{ByteOrder, LE};
+
+    reader
+        .seek(SeekFrom::Start(from))
+        .map_err(|_| OggOpusError::NotSeekable)?;
+
+    let mut window = [0u8; 4];
+    reader
+        .read_exact(&mut window)
+        .map_err(|_| Error::UnexpectedEof)?;
+    let mut start = from;
+    while window != CAPTURE_PATTERN {
+        window.copy_within(1.., 0);
+        reader
+            .read_exact(&mut window[3..])
+            .map_err(|_| Error::UnexpectedEof)?;
+        start += 1;
+    }
+
+    let mut header = [0u8; (PAGE_HEADER_LEN - 4) as usize];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| Error::UnexpectedEof)?;
+    let granule = LE::read_u64(&header[2..10]);
+    let segments = usize::from(header[22]);
+
+    let mut segment_table = vec![0u8; segments];
+    reader
+        .read_exact(&mut segment_table)
+        .map_err(|_| Error::UnexpectedEof)?;
+    let body_len: u64 = segment_table.iter().copied().map(u64::from).sum();
+
+    Ok(PageInfo {
+        start,
+        end: start + PAGE_HEADER_LEN + segments as u64 + body_len,
+        granule,
+    })
+}
+
+/// Scans backward from (not including) `before` for the page immediately preceding it.
+fn page_before<R>(reader: &mut R, before: u64) -> Result<PageInfo>
+where
+    R: Read + Seek,
+{
+    let mut candidate = before;
+    while candidate > 0 {
+        candidate -= 1;
+
+        reader
+            .seek(SeekFrom::Start(candidate))
+            .map_err(|_| OggOpusError::NotSeekable)?;
+        let mut window = [0u8; 4];
+        if reader.read_exact(&mut window).is_ok() && window == CAPTURE_PATTERN {
+            return scan_page(reader, candidate);
+        }
+    }
+
+    Err(Error::UnexpectedEof)
+}
+
 /// A reader for Ogg Opus files and/or streams.
 pub struct OggOpusReader<R: Read + Seek> {
-    reader: PacketReader<R>,
+    reader: Option<PacketReader<R>>,
     id_header: IdHeader,
     comments: CommentHeader,
 }
@@ -334,18 +883,35 @@ where
         };
 
         Ok(OggOpusReader {
-            reader,
+            reader: Some(reader),
             id_header,
             comments,
         })
     }
 
+    /// Returns the inner [`PacketReader`], which is only ever briefly `None` while
+    /// [`OggOpusReader::seek`] is repositioning it.
+    ///
+    /// [`PacketReader`]: https://docs.rs/ogg/*/ogg/reading/struct.PacketReader.html
+    /// [`OggOpusReader::seek`]: #method.seek
+    fn reader_mut(&mut self) -> &mut PacketReader<R> {
+        self.reader
+            .as_mut()
+            .expect("OggOpusReader used during a seek")
+    }
+
     /// Returns an iterator over user comments contained in the Vorbis comments block.
     #[inline]
     pub fn comments(&self) -> Comments<'_> {
         self.comments.comments()
     }
 
+    /// Returns typed access to well-known comment tags (e.g. `ARTIST`, `R128_TRACK_GAIN`).
+    #[inline]
+    pub fn tags(&self) -> Tags<'_> {
+        self.comments.tags()
+    }
+
     /// Returns an iterator over the contained audio frames.
     #[inline]
     pub fn frames(self) -> Frames<R> {
@@ -373,6 +939,14 @@ where
         self.id_header.output_gain()
     }
 
+    /// Returns the speaker position of each output channel, in channel order, or `None` for a
+    /// discrete-channel mapping, whose channel semantics the specification leaves
+    /// application-defined.
+    #[inline]
+    pub fn speaker_positions(&self) -> Option<Vec<SpeakerPosition>> {
+        self.id_header.speaker_positions()
+    }
+
     /// Returns the encoder vendor string from the Vorbis comment block.
     #[inline]
     pub fn vendor(&self) -> &str {
@@ -385,10 +959,181 @@ where
         self.id_header.version()
     }
 
+    /// Returns an iterator over decoded PCM samples, with the encoder delay and padding already
+    /// trimmed and the `OpusHead` output gain already applied.
+    ///
+    /// Only single-stream (non-multiplexed) Opus is supported so far; other streams return
+    /// [`OggOpusError::MultistreamUnsupported`].
+    ///
+    /// [`OggOpusError::MultistreamUnsupported`]: enum.OggOpusError.html#variant.MultistreamUnsupported
+    pub fn read_samples(self) -> Result<Samples<R>> {
+        if self.id_header.channels().mapping_table().streams() != 1 {
+            return Err(OggOpusError::MultistreamUnsupported.into());
+        }
+
+        let reader = self.reader.expect("OggOpusReader used during a seek");
+        Ok(Samples::new(reader, &self.id_header))
+    }
+
+    /// Seeks to `target`, an exact sample offset or a point in time from the start of the
+    /// decoded stream, returning the actual sample position landed on.
+    ///
+    /// Binary-searches the underlying stream for the Ogg page whose granule position brackets
+    /// `target`, then backs up a few pages so the decoder has the prior packets it needs to
+    /// re-establish its predictive state before the target is reached.
+    ///
+    /// Returns [`OggOpusError::NotSeekable`] if the underlying stream doesn't support seeking, or
+    /// [`OggOpusError::SeekPastEnd`] if `target` is past the end of the stream.
+    ///
+    /// [`OggOpusError::NotSeekable`]: enum.OggOpusError.html#variant.NotSeekable
+    /// [`OggOpusError::SeekPastEnd`]: enum.OggOpusError.html#variant.SeekPastEnd
+    pub fn seek<T>(&mut self, target: T) -> Result<u64>
+    where
+        T: Into<SeekTarget>,
+    {
+        let pre_skip = u64::from(self.id_header.pre_skip());
+        let target_granule = target.into().as_samples() + pre_skip;
+        let mut raw = self
+            .reader
+            .take()
+            .expect("OggOpusReader used during a seek")
+            .into_inner();
+
+        let resume_at = Self::find_resume_point(&mut raw, target_granule);
+
+        // rebuild the `PacketReader` regardless of outcome, so a failed seek leaves the
+        // `OggOpusReader` usable rather than permanently "mid-seek"
+        let (resume_at, landed_granule) = match resume_at {
+            Ok(resume_at) => resume_at,
+            Err(err) => {
+                self.reader = Some(PacketReader::new(raw));
+                return Err(err);
+            }
+        };
+
+        raw.seek(SeekFrom::Start(resume_at))
+            .map_err(|_| OggOpusError::NotSeekable)?;
+        self.reader = Some(PacketReader::new(raw));
+
+        Ok(landed_granule.saturating_sub(pre_skip))
+    }
+
+    /// Seeks to an exact sample offset (at 48 kHz) from the start of the decoded stream.
+    ///
+    /// A thin wrapper around [`OggOpusReader::seek`] for callers working in sample counts rather
+    /// than [`SeekTarget`]; like [`SeekTarget::Sample`], `granule` excludes the stream's pre-skip,
+    /// so it is not a raw Ogg granule position.
+    ///
+    /// [`OggOpusReader::seek`]: #method.seek
+    #[inline]
+    pub fn seek_to_granule(&mut self, granule: u64) -> Result<u64> {
+        self.seek(granule)
+    }
+
+    /// Seeks to `ms` milliseconds from the start of the decoded stream.
+    ///
+    /// A thin wrapper around [`OggOpusReader::seek`] for callers working in milliseconds rather
+    /// than a [`Duration`].
+    ///
+    /// [`OggOpusReader::seek`]: #method.seek
+    #[inline]
+    pub fn seek_to_ms(&mut self, ms: u64) -> Result<u64> {
+        self.seek(Duration::from_millis(ms))
+    }
+
+    /// Finds the byte offset to resume decoding from in order to reach `target_granule`, along
+    /// with the granule position of the page landed on.
+    ///
+    /// Binary-searches for the earliest page whose granule position is at or past
+    /// `target_granule`, then backs up a few pages so the decoder can re-establish its predictive
+    /// state before the target is actually reached.
+    fn find_resume_point(raw: &mut R, target_granule: u64) -> Result<(u64, u64)> {
+        /// Number of pages to back up from the found page, to give the decoder prior packets to
+        /// re-establish inter-frame prediction and overlap before the target is reached.
+        const LOOKBACK_PAGES: u32 = 2;
+
+        let stream_len = raw
+            .seek(SeekFrom::End(0))
+            .map_err(|_| OggOpusError::NotSeekable)?;
+
+        // reject a seek past the end of the stream, rather than silently clamping it or landing
+        // on the wrong page
+        let last_page = page_before(raw, stream_len)?;
+        if last_page.granule != u64::max_value() && target_granule > last_page.granule {
+            return Err(OggOpusError::SeekPastEnd.into());
+        }
+
+        let (mut lo, mut hi) = (0, stream_len);
+        let mut found = scan_page(raw, 0)?;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match scan_page(raw, mid) {
+                Ok(page) if page.granule != u64::max_value() && page.granule >= target_granule => {
+                    hi = page.start;
+                    found = page;
+                }
+                Ok(page) => lo = page.end.max(mid + 1),
+                // nothing parseable between `mid` and the end of the stream
+                Err(_) => hi = mid,
+            }
+        }
+
+        let mut resume_at = found.start;
+        for _ in 0..LOOKBACK_PAGES {
+            resume_at = match page_before(raw, resume_at) {
+                Ok(page) => page.start,
+                Err(_) => break,
+            };
+        }
+
+        Ok((resume_at, found.granule))
+    }
+
+    /// Returns the total number of samples (at 48 kHz) in the decoded stream, or `None` if the
+    /// underlying stream doesn't support seeking, or is a live stream with no final page yet.
+    ///
+    /// Seeks the underlying reader to the last Ogg page to read its granule position, then
+    /// restores the reader's prior position.
+    pub fn total_samples(&mut self) -> Option<u64> {
+        let pre_skip = u64::from(self.id_header.pre_skip());
+        let mut raw = self
+            .reader
+            .take()
+            .expect("OggOpusReader used during a seek")
+            .into_inner();
+
+        let granule = raw.seek(SeekFrom::Current(0)).ok().and_then(|current| {
+            let stream_len = raw.seek(SeekFrom::End(0)).ok()?;
+            let last_page = page_before(&mut raw, stream_len).ok()?;
+            raw.seek(SeekFrom::Start(current)).ok()?;
+
+            if last_page.granule == u64::max_value() {
+                None
+            } else {
+                Some(last_page.granule.saturating_sub(pre_skip))
+            }
+        });
+
+        self.reader = Some(PacketReader::new(raw));
+        granule
+    }
+
+    /// Returns the total duration, in milliseconds, of the decoded stream, under the same
+    /// conditions as [`OggOpusReader::total_samples`].
+    ///
+    /// [`OggOpusReader::total_samples`]: #method.total_samples
+    #[inline]
+    pub fn duration_ms(&mut self) -> Option<u64> {
+        self.total_samples()
+            .map(|samples| samples * 1000 / u64::from(DECODE_SAMPLE_RATE))
+    }
+
     /// Returns the wrapped reader, consuming the `OggOpusReader`.
     #[inline]
     pub fn into_inner(self) -> R {
-        self.reader.into_inner()
+        self.reader
+            .expect("OggOpusReader used during a seek")
+            .into_inner()
     }
 }
 
@@ -397,14 +1142,6 @@ where
     R: Read + Seek,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        #[derive(PartialEq, Eq, Clone, Copy, Hash)]
-        struct ElidedStruct<'a>(&'a str);
-        impl Debug for ElidedStruct<'_> {
-            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-                f.pad(self.0)
-            }
-        }
-
         f.debug_struct("OggOpusReader")
             .field("reader", &ElidedStruct("PacketReader"))
             .field("id_header", &self.id_header)
@@ -412,3 +1149,215 @@ where
             .finish()
     }
 }
+
+/// A writer for Ogg Opus files and/or streams.
+///
+/// Writes a correct [RFC 7845] stream: an `OpusHead` page and an `OpusTags` page, written
+/// immediately by [`OggOpusWriter::new`], followed by the packets passed to
+/// [`OggOpusWriter::write_packet`], which the underlying Ogg writer laces into pages (not one page
+/// per call), each packet carrying the running absolute granule position (samples at 48 kHz,
+/// accounting for pre-skip). [`OggOpusWriter::finish`] flushes the final packet as the last page
+/// of the logical stream.
+///
+/// [RFC 7845]: https://tools.ietf.org/html/rfc7845
+pub struct OggOpusWriter<W: Write> {
+    writer: PacketWriter<W>,
+    serial: u32,
+    granule: u64,
+    /// The most recently written packet and the granule position it should be written with,
+    /// held back so it can be marked as ending the stream if [`OggOpusWriter::finish`] is called
+    /// next, rather than being followed by another packet.
+    ///
+    /// [`OggOpusWriter::finish`]: #method.finish
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl<W> OggOpusWriter<W>
+where
+    W: Write,
+{
+    /// Creates a new `OggOpusWriter`, immediately writing the `OpusHead` and `OpusTags` pages to
+    /// `inner`.
+    ///
+    /// `serial` identifies this logical Ogg bitstream and should be chosen at random when muxing
+    /// a fresh file. `channels`, `family`, and `mapping_table` describe the channel mapping
+    /// family and table exactly as they appear on the wire in an `OpusHead` packet; `pre_skip`,
+    /// `sample_rate`, and `output_gain` populate the rest of it. `vendor` and `comments` populate
+    /// the `OpusTags` packet.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<'a, I>(
+        inner: W,
+        serial: u32,
+        channels: u8,
+        family: u8,
+        mapping_table: &[u8],
+        pre_skip: u16,
+        sample_rate: Option<NonZeroU32>,
+        output_gain: i16,
+        vendor: &str,
+        comments: I,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let id_header = IdHeader {
+            version: 1,
+            channels: ChannelMapping::new(channels, family, mapping_table)?,
+            pre_skip,
+            sample_rate,
+            output_gain,
+        };
+
+        let mut writer = PacketWriter::new(inner);
+
+        let mut id_packet = Vec::new();
+        id_header.write(&mut id_packet);
+        writer
+            .write_packet(id_packet, serial, PacketWriteEndInfo::EndPage, 0)
+            .map_err(|_| Error::UnexpectedEof)?;
+
+        let mut comments_packet = Vec::new();
+        write_comment_header(&mut comments_packet, vendor, comments);
+        writer
+            .write_packet(comments_packet, serial, PacketWriteEndInfo::EndPage, 0)
+            .map_err(|_| Error::UnexpectedEof)?;
+
+        Ok(OggOpusWriter {
+            writer,
+            serial,
+            granule: u64::from(pre_skip),
+            pending: None,
+        })
+    }
+
+    /// Writes one encoded Opus packet, advancing the running granule position by the packet's
+    /// duration.
+    ///
+    /// The packet isn't necessarily flushed to a page immediately; it, and the granule position
+    /// it should be written with, are held until the next call, so that the true last packet can
+    /// be marked as ending the stream by [`OggOpusWriter::finish`] rather than this one.
+    ///
+    /// [`OggOpusWriter::finish`]: #method.finish
+    pub fn write_packet(&mut self, data: &[u8]) -> Result<()> {
+        self.granule += OpusPacket::new(data)?.samples();
+
+        if let Some((pending, granule)) = self.pending.take() {
+            self.writer
+                .write_packet(
+                    pending,
+                    self.serial,
+                    PacketWriteEndInfo::NormalPacket,
+                    granule,
+                )
+                .map_err(|_| Error::UnexpectedEof)?;
+        }
+
+        self.pending = Some((data.to_owned(), self.granule));
+        Ok(())
+    }
+
+    /// Flushes the final buffered packet as the last page of the logical stream, then returns the
+    /// wrapped writer.
+    pub fn finish(mut self) -> Result<W> {
+        if let Some((pending, granule)) = self.pending.take() {
+            self.writer
+                .write_packet(pending, self.serial, PacketWriteEndInfo::EndStream, granule)
+                .map_err(|_| Error::UnexpectedEof)?;
+        }
+
+        Ok(self.writer.into_inner())
+    }
+}
+
+impl<W> Debug for OggOpusWriter<W>
+where
+    W: Write,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OggOpusWriter")
+            .field("writer", &ElidedStruct("PacketWriter"))
+            .field("serial", &self.serial)
+            .field("granule", &self.granule)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A minimal, internally-framed, single-frame Opus packet `Packet::new` accepts: TOC (config
+    /// 0, mono, code 0) followed by one byte of frame data.
+    const OPUS_PACKET: &[u8] = &[0x00, 0xaa];
+
+    #[test]
+    fn writer_produces_a_stream_the_reader_accepts() {
+        let mut writer = OggOpusWriter::new(
+            Vec::new(),
+            0x1234_5678,
+            1,
+            0,
+            &[],
+            0,
+            NonZeroU32::new(48_000),
+            0,
+            "test-vendor",
+            vec![("TITLE", "hello")],
+        )
+        .unwrap();
+
+        writer.write_packet(OPUS_PACKET).unwrap();
+        writer.write_packet(OPUS_PACKET).unwrap();
+        let buf = writer.finish().unwrap();
+
+        let reader = OggOpusReader::new(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.vendor(), "test-vendor");
+        assert_eq!(reader.tags().title(), Some("hello"));
+        assert_eq!(reader.pre_skip(), 0);
+        assert_eq!(reader.sample_rate(), NonZeroU32::new(48_000));
+    }
+
+    #[test]
+    fn writer_advances_granule_position_by_packet_duration() {
+        let mut writer = OggOpusWriter::new(
+            Vec::new(),
+            1,
+            1,
+            0,
+            &[],
+            0,
+            None,
+            0,
+            "test-vendor",
+            Vec::new(),
+        )
+        .unwrap();
+
+        let packet_samples = OpusPacket::new(OPUS_PACKET).unwrap().samples();
+        writer.write_packet(OPUS_PACKET).unwrap();
+        assert_eq!(writer.granule, packet_samples);
+
+        writer.write_packet(OPUS_PACKET).unwrap();
+        assert_eq!(writer.granule, 2 * packet_samples);
+    }
+
+    #[test]
+    fn writer_new_rejects_bad_channel_mapping() {
+        assert!(OggOpusWriter::new(
+            Vec::new(),
+            1,
+            // 0 channels isn't a valid RTP layout
+            0,
+            0,
+            &[],
+            0,
+            None,
+            0,
+            "test-vendor",
+            Vec::new(),
+        )
+        .is_err());
+    }
+}
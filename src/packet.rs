@@ -2,15 +2,23 @@
 
 use crate::{
     channel::MappingTable,
+    ec::RangeDecoder,
     error::{Error, Result},
+    sample::{Sample, Samples},
+    silk::SilkDecoder,
     slice_ext::SliceExt,
 };
-use std::{
-    error,
+use core::{
     fmt::{self, Debug, Display, Formatter},
     time::Duration,
 };
 
+#[cfg(feature = "std")]
+use std::error;
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{vec, vec::Vec};
+
 /// A packet's Config Number, from [RFC 6716 § 3.1]
 ///
 /// [RFC 6716 § 3.1]: https://tools.ietf.org/html/rfc6716#section-3.1
@@ -19,7 +27,7 @@ struct ConfigNumber(u8);
 
 impl ConfigNumber {
     fn new(config: u8) -> Option<ConfigNumber> {
-        use std::u8::MAX;
+        use core::u8::MAX;
 
         match config {
             0..=31 => Some(ConfigNumber(config)),
@@ -66,7 +74,7 @@ enum Mode {
 
 impl From<ConfigNumber> for Mode {
     fn from(config: ConfigNumber) -> Mode {
-        use std::u8::MAX;
+        use core::u8::MAX;
 
         // See Table 2 of RFC 6716
         match config.into() {
@@ -101,7 +109,7 @@ enum Bandwidth {
 
 impl From<ConfigNumber> for Bandwidth {
     fn from(config: ConfigNumber) -> Bandwidth {
-        use std::u8::MAX;
+        use core::u8::MAX;
 
         // See Table 2 of RFC 6716
         match config.into() {
@@ -171,7 +179,7 @@ impl Debug for FrameSize {
 
 impl From<ConfigNumber> for FrameSize {
     fn from(config: ConfigNumber) -> FrameSize {
-        use std::u8::MAX;
+        use core::u8::MAX;
 
         // See Table 2 of RFC 6716
         match config.into() {
@@ -229,7 +237,7 @@ enum FramesLayout {
 
 impl FramesLayout {
     fn new(c: u8) -> Option<FramesLayout> {
-        use std::u8::MAX;
+        use core::u8::MAX;
 
         // See Page 15 of RFC 6716
         match c {
@@ -401,6 +409,7 @@ impl Display for MalformedPacketError {
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for MalformedPacketError {}
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -483,7 +492,7 @@ impl Packet {
         let mut offset = 0;
 
         while let Some(byte) = data.get(offset) {
-            use std::u8::MAX;
+            use core::u8::MAX;
 
             match *byte {
                 MAX => padding += 254,
@@ -723,6 +732,19 @@ impl Packet {
             .into_iter()
             .map(move |slice| Frame::new(config, stereo, &slice[..]))
     }
+
+    /// Returns this packet's total duration, in samples at the fixed 48 kHz Opus decode rate.
+    ///
+    /// Used by Ogg Opus muxers and demuxers to advance a running granule position by exactly one
+    /// packet at a time.
+    pub(crate) fn samples(&self) -> u64 {
+        /// Sample rate, in Hz, that Opus granule positions are always expressed in.
+        const DECODE_SAMPLE_RATE: u64 = 48_000;
+
+        u64::from(self.config.frame_size.as_microseconds()) * self.frames.len() as u64
+            * DECODE_SAMPLE_RATE
+            / 1_000_000
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -756,3 +778,120 @@ impl Multistream {
         self.packets.into_iter().rev().map(Packet::frames).flatten()
     }
 }
+
+/// Decodes a single-stream Opus packet sequence into PCM samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Decoder {
+    sample_rate: u32,
+    channels: u8,
+    max_samples: usize,
+    /// Retains the state packet-loss concealment needs across packets: the SILK layer's own
+    /// per-channel concealment gains, alongside its `stereo_pred_weights`.
+    silk: SilkDecoder,
+    /// The most recently decoded packet's frame size, used to size concealment for a lost packet
+    /// in place of assuming `FrameSize::default()`.
+    last_frame_size: FrameSize,
+}
+
+impl Decoder {
+    /// The default for `max_samples`, comfortably above the most any single packet can
+    /// legitimately need: 120 ms (the maximum packet duration, [RFC 6716 § 3.4:R5]) at 192 kHz
+    /// across 255 channels.
+    ///
+    /// [RFC 6716 § 3.4:R5]: https://tools.ietf.org/html/rfc6716#ref-R5
+    pub const DEFAULT_MAX_SAMPLES: usize = 5_875_200;
+
+    /// Creates a decoder outputting PCM at `sample_rate`, with `channels` channels.
+    pub fn new(sample_rate: u32, channels: u8) -> Decoder {
+        Decoder::with_max_samples(sample_rate, channels, Decoder::DEFAULT_MAX_SAMPLES)
+    }
+
+    /// Creates a decoder as with `Decoder::new`, but rejecting any `decode` call that would need
+    /// to allocate more than `max_samples` interleaved samples.
+    ///
+    /// Lowering this below `Decoder::DEFAULT_MAX_SAMPLES` bounds how much memory a single
+    /// maliciously-crafted packet can force a `decode` call to request.
+    pub fn with_max_samples(sample_rate: u32, channels: u8, max_samples: usize) -> Decoder {
+        Decoder {
+            sample_rate,
+            channels,
+            max_samples,
+            silk: SilkDecoder::new(channels > 1),
+            last_frame_size: FrameSize::default(),
+        }
+    }
+
+    /// Returns the number of samples per channel one frame of `frame_size` decodes to at this
+    /// decoder's `sample_rate`.
+    fn frame_samples_per_channel(&self, frame_size: FrameSize) -> usize {
+        usize::from(frame_size.as_microseconds())
+            .saturating_mul(self.sample_rate as usize)
+            / 1_000_000
+    }
+
+    /// Returns the number of interleaved samples decoding `packet` would append to `buf`, or, for
+    /// packet-loss concealment (`packet` is `None`), a single frame at the last decoded packet's
+    /// frame size.
+    fn samples_needed(&self, packet: Option<&Packet>) -> usize {
+        let (frame_count, frame_size) = match packet {
+            Some(packet) => (packet.frames.len(), packet.config.frame_size),
+            None => (1, self.last_frame_size),
+        };
+
+        frame_count
+            .saturating_mul(self.frame_samples_per_channel(frame_size))
+            .saturating_mul(usize::from(self.channels))
+    }
+
+    /// Decodes `packet` into `buf`, appending interleaved PCM samples, and returns the number of
+    /// samples written.
+    ///
+    /// Passing `None` signals a lost packet, triggering packet-loss concealment in place of a
+    /// real decode.
+    ///
+    /// **Not yet implemented for real packets:** SILK/CELT synthesis doesn't exist yet, so passing
+    /// `Some(packet)` always returns [`Error::Unsupported`] once SILK's bitstream decode finishes
+    /// running; see the crate-level docs for the current state of this gap. Only the `None` path
+    /// above produces real PCM samples today.
+    ///
+    /// [`Error::Unsupported`]: ../error/enum.Error.html#variant.Unsupported
+    pub fn decode<S, T>(&mut self, packet: Option<Packet>, buf: &mut S) -> Result<usize>
+    where
+        S: Samples<T>,
+        T: Sample,
+    {
+        let needed = self.samples_needed(packet.as_ref());
+        if needed > self.max_samples {
+            // Don't hand the allocator the packet's own (possibly wildly exaggerated) sample
+            // count—request something guaranteed to fail instead, so the reservation is rejected
+            // immediately rather than actually attempting a multi-gigabyte allocation.
+            buf.try_reserve_samples(usize::max_value())?;
+        } else {
+            buf.try_reserve_samples(needed)?;
+        }
+
+        if let Some(packet) = packet {
+            self.last_frame_size = packet.config.frame_size;
+
+            if packet.config.mode != Mode::Celt {
+                for frame in &packet.frames {
+                    let mut data = RangeDecoder::new(frame);
+                    self.silk.decode(&mut data, packet.config, packet.stereo)?;
+                }
+            }
+
+            // TODO: SILK/CELT synthesis isn't implemented yet; report the gap instead of
+            // panicking so callers can fall back to concealment rather than crash on real audio.
+            // The SILK decode above still ran, though, so packet-loss concealment for whatever
+            // comes after this packet has real signal-type and frame-size state to work from
+            // instead of guessing.
+            Err(Error::Unsupported)
+        } else {
+            let samples_per_channel = self.frame_samples_per_channel(self.last_frame_size);
+            for sample in self.silk.conceal(samples_per_channel) {
+                buf.push_sample(T::from_pcm16(sample));
+            }
+            Ok(needed)
+        }
+    }
+}
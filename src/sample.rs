@@ -1,9 +1,145 @@
-pub trait Sample {}
+use crate::error::{Error, Result};
+use core::mem;
 
-impl Sample for f32 {}
-impl Sample for i16 {}
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
 
-pub trait Samples<T: Sample> {}
+pub trait Sample: Copy {
+    /// Converts a linear 16-bit PCM value—the scale packet-loss concealment synthesizes
+    /// in—into this sample type.
+    fn from_pcm16(value: i16) -> Self;
 
-impl<'a, T> Samples<T> for &'a mut [T] where T: Sample {}
-impl<T> Samples<T> for Vec<T> where T: Sample {}
+    /// Converts a normalized `f32` value—the scale channel routing mixes down in—into this
+    /// sample type.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for f32 {
+    fn from_pcm16(value: i16) -> f32 {
+        f32::from(value) / 32_768.0
+    }
+
+    fn from_f32(value: f32) -> f32 {
+        value
+    }
+}
+
+impl Sample for i16 {
+    fn from_pcm16(value: i16) -> i16 {
+        value
+    }
+
+    fn from_f32(value: f32) -> i16 {
+        (value * 32_768.0).round().clamp(-32_768.0, 32_767.0) as i16
+    }
+}
+
+pub trait Samples<T: Sample> {
+    /// Reserves capacity for at least `additional` more samples, returning
+    /// [`Error::AllocationFailed`] if the allocator reports failure rather than aborting or
+    /// unwinding.
+    ///
+    /// [`Error::AllocationFailed`]: ../error/enum.Error.html#variant.AllocationFailed
+    fn try_reserve_samples(&mut self, additional: usize) -> Result<()>;
+
+    /// Appends `sample` as the next interleaved PCM sample.
+    ///
+    /// Callers must not push more samples than were reserved by a preceding
+    /// [`Samples::try_reserve_samples`] call.
+    ///
+    /// [`Samples::try_reserve_samples`]: #method.try_reserve_samples
+    fn push_sample(&mut self, sample: T);
+}
+
+impl<'a, T> Samples<T> for &'a mut [T]
+where
+    T: Sample,
+{
+    fn try_reserve_samples(&mut self, additional: usize) -> Result<()> {
+        // a borrowed slice is already fully allocated; there's nothing to reserve, but make sure
+        // what's left of it can actually hold `additional` more samples, since push_sample has no
+        // way to fail if it can't
+        if additional > self.len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        Ok(())
+    }
+
+    fn push_sample(&mut self, sample: T) {
+        // advance past the written sample by splitting it off the front of the remaining slice
+        if let Some((first, rest)) = mem::take(self).split_first_mut() {
+            *first = sample;
+            *self = rest;
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl<T> Samples<T> for Vec<T>
+where
+    T: Sample,
+{
+    fn try_reserve_samples(&mut self, additional: usize) -> Result<()> {
+        self.try_reserve(additional)
+            .map_err(Error::AllocationFailed)
+    }
+
+    fn push_sample(&mut self, sample: T) {
+        self.push(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f32_from_pcm16_scales_to_unit_range() {
+        assert_eq!(f32::from_pcm16(0), 0.0);
+        assert_eq!(f32::from_pcm16(i16::MAX), i16::MAX as f32 / 32_768.0);
+        assert_eq!(f32::from_pcm16(i16::MIN), -1.0);
+    }
+
+    #[test]
+    fn i16_from_pcm16_is_identity() {
+        assert_eq!(i16::from_pcm16(12345), 12345);
+    }
+
+    #[test]
+    fn i16_from_f32_clamps_out_of_range_values() {
+        assert_eq!(i16::from_f32(0.0), 0);
+        assert_eq!(i16::from_f32(2.0), i16::MAX);
+        assert_eq!(i16::from_f32(-2.0), i16::MIN);
+    }
+
+    #[test]
+    fn mut_slice_try_reserve_rejects_too_few_remaining_samples() {
+        let mut buf = [0i16; 2];
+        let mut samples: &mut [i16] = &mut buf;
+        assert!(samples.try_reserve_samples(2).is_ok());
+        assert!(matches!(
+            samples.try_reserve_samples(3),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn mut_slice_push_sample_writes_in_order_and_advances() {
+        let mut buf = [0i16; 3];
+        let mut samples: &mut [i16] = &mut buf;
+        samples.push_sample(1);
+        samples.push_sample(2);
+        samples.push_sample(3);
+        assert_eq!(buf, [1, 2, 3]);
+    }
+
+    #[test]
+    fn vec_try_reserve_then_push_sample_appends() {
+        let mut samples: Vec<i16> = Vec::new();
+        samples.try_reserve_samples(2).unwrap();
+        samples.push_sample(1);
+        samples.push_sample(2);
+        assert_eq!(samples, [1, 2]);
+    }
+}
@@ -3,8 +3,11 @@ use crate::{
     silk::{Channel, LpHeader},
 };
 
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
-enum SignalType {
+pub(super) enum SignalType {
     Inactive,
     Unvoiced,
     Voiced,
@@ -64,32 +67,105 @@ impl StereoPredWeights {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-pub(super) struct SubframeGains;
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub(super) struct SubframeGains {
+    /// The dequantized Q16 gain for each subframe, in subframe order.
+    gains_q16: Vec<i32>,
+}
 
 impl SubframeGains {
-    fn from_stream(data: &mut RangeDecoder<'_>, signal_type: SignalType) -> SubframeGains {
+    fn from_stream(
+        data: &mut RangeDecoder<'_>,
+        signal_type: SignalType,
+        subframes: u8,
+        prev_log_gain: &mut Option<u8>,
+    ) -> SubframeGains {
         const ICDF_SUBFR_INDEPENDENT_INACTIVE: &[u8] = &[224, 112, 44, 15, 3, 2, 1, 0];
         const ICDF_SUBFR_INDEPENDENT_UNVOICED: &[u8] = &[254, 237, 192, 132, 70, 23, 4, 0];
         const ICDF_SUBFR_INDEPENDENT_VOICED: &[u8] = &[255, 252, 226, 155, 61, 11, 2, 0];
         const ICDF_SUBFR_INDEPENDENT_COMMON: &[u8] = &[224, 192, 160, 128, 96, 64, 32, 0];
+        // RFC 6716 § 4.2.7.4's icdf for the delta between a subframe's log-gain and the one
+        // before it.
+        const ICDF_SUBFR_DELTA: &[u8] = &[
+            250, 245, 234, 203, 71, 50, 42, 38, 35, 33, 31, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20,
+            19, 18, 17, 16, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+        ];
+
+        let gains_q16 = (0..subframes)
+            .map(|subframe| {
+                let log_gain = if subframe == 0 {
+                    let independent_icdf = match signal_type {
+                        SignalType::Inactive => ICDF_SUBFR_INDEPENDENT_INACTIVE,
+                        SignalType::Unvoiced => ICDF_SUBFR_INDEPENDENT_UNVOICED,
+                        SignalType::Voiced => ICDF_SUBFR_INDEPENDENT_VOICED,
+                    };
+                    let msb = data.decode_icdf(independent_icdf, 8).unwrap();
+                    let lsb = data.decode_icdf(ICDF_SUBFR_INDEPENDENT_COMMON, 8).unwrap();
+                    let gain_index = ((msb << 3) | lsb) as u8;
+
+                    match *prev_log_gain {
+                        Some(prev) => gain_index.max(prev.saturating_sub(16)),
+                        None => gain_index,
+                    }
+                } else {
+                    let delta = data.decode_icdf(ICDF_SUBFR_DELTA, 8).unwrap() as i32;
+                    let prev = i32::from(prev_log_gain.unwrap());
+                    (2 * delta - 16).max(prev + delta - 4).max(0).min(63) as u8
+                };
+
+                *prev_log_gain = Some(log_gain);
+
+                log2lin((0x1D1C * i32::from(log_gain) >> 16) + 2090)
+            })
+            .collect();
+
+        SubframeGains { gains_q16 }
+    }
 
-        unimplemented!()
+    /// Returns the dequantized Q16 gain of this frame's last subframe—the gain packet-loss
+    /// concealment scales its synthesized noise at, since it's the closest real measurement to
+    /// whatever comes right after this frame.
+    pub(super) fn last_q16(&self) -> i32 {
+        *self
+            .gains_q16
+            .last()
+            .expect("a frame always has at least one subframe")
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+/// Converts a Q7 logarithmic gain to a linear Q16 magnitude, via SILK's piecewise-linear
+/// approximation of `2^x` (the `log2lin` routine of [RFC 6716 § 4.1.6]).
+///
+/// [RFC 6716 § 4.1.6]: https://tools.ietf.org/html/rfc6716#section-4.1.6
+fn log2lin(log_q7: i32) -> i32 {
+    use core::i32::MAX;
+
+    if log_q7 < 0 {
+        0
+    } else if log_q7 >= 3967 {
+        MAX
+    } else {
+        let out = 1_i32 << (log_q7 >> 7);
+        let frac_q7 = log_q7 & 0x7F;
+        out + ((out * frac_q7) >> 7)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub(super) struct SilkFrame {
+    channel: Channel,
     stereo_pred_weights: Option<StereoPredWeights>,
     mid_only: Option<bool>,
     signal_type: SignalType,
     quantization_offset_type: QuantizationOffsetType,
+    gains: SubframeGains,
 }
 
 impl SilkFrame {
     pub(super) fn from_stream(
         data: &mut RangeDecoder<'_>,
         env: SilkFrameEnvironment<'_>,
+        prev_log_gain: &mut Option<u8>,
     ) -> SilkFrame {
         let stereo_mid = |data: &mut RangeDecoder<'_>| {
             const ICDF_MID_ONLY: &[u8] = &[64, 0];
@@ -150,18 +226,38 @@ impl SilkFrame {
 
         let (stereo_pred_weights, mid_only) = stereo_mid(data);
         let (signal_type, quantization_offset_type) = frame_type(data, vad);
+        let gains = SubframeGains::from_stream(data, signal_type, env.subframes, prev_log_gain);
 
         SilkFrame {
+            channel: env.channel,
             stereo_pred_weights,
             mid_only,
             signal_type,
             quantization_offset_type,
+            gains,
         }
     }
 
+    /// Returns which channel this frame belongs to; used to route it to the right per-channel
+    /// packet-loss concealment state.
+    pub(super) fn channel(&self) -> Channel {
+        self.channel
+    }
+
     pub(super) fn mid_only(&self) -> Option<bool> {
         self.mid_only
     }
+
+    /// Returns whether this frame was coded as inactive, unvoiced, or voiced; used by
+    /// packet-loss concealment to decide how to extrapolate a later lost frame.
+    pub(super) fn signal_type(&self) -> SignalType {
+        self.signal_type
+    }
+
+    /// Returns the dequantized Q16 gain of each subframe, in subframe order.
+    pub(super) fn gains(&self) -> &SubframeGains {
+        &self.gains
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -171,4 +267,5 @@ pub(super) struct SilkFrameEnvironment<'a> {
     pub(super) lbrr: bool,
     pub(super) lp_header: &'a LpHeader,
     pub(super) stereo: bool,
+    pub(super) subframes: u8,
 }
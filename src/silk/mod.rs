@@ -2,15 +2,20 @@ use crate::{
     ec::RangeDecoder,
     packet::{Bandwidth, Config, FrameSize},
 };
-use std::{
-    error::Error,
+use core::{
     fmt::{self, Display, Formatter},
     iter::FusedIterator,
 };
 
+#[cfg(feature = "std")]
+use std::error::Error;
+
 mod frame;
 
-use self::frame::{SilkFrame, StereoPredWeights};
+use self::frame::{SignalType, SilkFrame, StereoPredWeights};
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub enum SilkError {
@@ -23,6 +28,7 @@ impl Display for SilkError {
     }
 }
 
+#[cfg(feature = "std")]
 impl Error for SilkError {
     fn description(&self) -> &str {
         match self {
@@ -94,6 +100,14 @@ impl LbrrFrameHeader {
             })
             .map(|lbrr_sym| LbrrFrameHeader(lbrr_sym as u8 + 1))
     }
+
+    /// Returns whether `frame_no` has an LBRR copy coded for it, per the bitmask decoded by
+    /// [`LbrrFrameHeader::from_stream`].
+    ///
+    /// [`LbrrFrameHeader::from_stream`]: #method.from_stream
+    fn lbrr(self, frame_no: u8) -> bool {
+        self.0 & (1 << frame_no) != 0
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -141,8 +155,8 @@ impl LpHeader {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-struct SilkPacket<'a, 'b> {
+#[derive(Debug)]
+struct SilkPacket<'a, 'b, 'c> {
     bandwidth: Bandwidth,
     data: &'a mut RangeDecoder<'b>,
     lp_header: LpHeader,
@@ -151,14 +165,34 @@ struct SilkPacket<'a, 'b> {
     frames: u8,
     cur_frame: u8,
     subframes: u8,
+
+    /// Cursor over the (channel, frame) pairs that may carry an embedded LBRR copy, enumerated
+    /// mid-channel-first in frame-ascending order, then side-channel the same way. Consumed by
+    /// [`SilkPacket::next_lbrr`] before any primary frame is decoded.
+    ///
+    /// [`SilkPacket::next_lbrr`]: #method.next_lbrr
+    lbrr_cursor: u8,
+    lbrr_len: u8,
+
+    /// The last-decoded subframe's log-gain for the mid channel, carried forward as
+    /// [`frame::SilkFrame::from_stream`]'s delta-coding state. Borrowed from [`SilkDecoder`] so
+    /// it persists across packets, per RFC 6716 § 4.2.7.4's independent-gain clamp against the
+    /// *previous packet's* final subframe, not just the previous subframe within this packet.
+    ///
+    /// [`SilkDecoder`]: struct.SilkDecoder.html
+    mid_prev_log_gain: &'c mut Option<u8>,
+    /// Same as `mid_prev_log_gain`, for the side channel.
+    side_prev_log_gain: &'c mut Option<u8>,
 }
 
-impl<'a, 'b> SilkPacket<'a, 'b> {
+impl<'a, 'b, 'c> SilkPacket<'a, 'b, 'c> {
     fn from_stream(
         data: &'a mut RangeDecoder<'b>,
         config: Config,
         stereo: bool,
-    ) -> Result<SilkPacket<'a, 'b>, SilkError> {
+        mid_prev_log_gain: &'c mut Option<u8>,
+        side_prev_log_gain: &'c mut Option<u8>,
+    ) -> Result<SilkPacket<'a, 'b, 'c>, SilkError> {
         let (frames, subframes) = match config.frame_size() {
             FrameSize::Ten => (1, 2),
             FrameSize::Twenty => (1, 4),
@@ -177,48 +211,184 @@ impl<'a, 'b> SilkPacket<'a, 'b> {
             frames,
             cur_frame: 0,
             subframes,
+            lbrr_cursor: 0,
+            lbrr_len: frames * if stereo { 2 } else { 1 },
+            mid_prev_log_gain,
+            side_prev_log_gain,
         })
     }
-}
 
-impl<'a, 'b> Iterator for SilkPacket<'a, 'b> {
-    type Item = SilkFrame;
+    /// Returns whether `channel`'s copy of `frame_no` has an LBRR copy embedded in this packet,
+    /// per the channel's [`LpChannelHeader::lbrr`] flag and, for 40 ms/60 ms packets, the
+    /// per-frame [`LbrrFrameHeader`] bitmask.
+    ///
+    /// [`LpChannelHeader::lbrr`]: struct.LpChannelHeader.html#method.lbrr
+    fn lbrr_present(&self, channel: Channel, frame_no: u8) -> bool {
+        match self.lp_header.channel(channel) {
+            Some(channel_header) if channel_header.lbrr() => {
+                match self.lp_header.lbrr(channel) {
+                    Some(lbrr_flags) => lbrr_flags.lbrr(frame_no),
+                    // 10 ms/20 ms packets have a single frame, implicitly flagged by the
+                    // per-channel LBRR bit alone; no per-frame bitmask is coded for them.
+                    None => true,
+                }
+            }
+            _ => false,
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Decodes and returns the next embedded Low Bit-Rate Redundancy frame, carrying a
+    /// reconstruction of the *previous* packet, or `None` once every (channel, frame) pair with
+    /// a coded LBRR copy has been consumed.
+    fn next_lbrr(&mut self) -> Option<SilkFrame> {
+        use self::frame::SilkFrameEnvironment;
+
+        while self.lbrr_cursor < self.lbrr_len {
+            let cursor = self.lbrr_cursor;
+            self.lbrr_cursor += 1;
+
+            let channel = if cursor < self.frames {
+                Channel::Mid
+            } else {
+                Channel::Side
+            };
+            let frame_no = cursor % self.frames;
+
+            if self.lbrr_present(channel, frame_no) {
+                let env = SilkFrameEnvironment {
+                    channel,
+                    frame_no,
+                    lbrr: true,
+                    lp_header: &self.lp_header,
+                    stereo: self.stereo,
+                    subframes: self.subframes,
+                };
+                let prev_log_gain = match channel {
+                    Channel::Mid => &mut *self.mid_prev_log_gain,
+                    Channel::Side => &mut *self.side_prev_log_gain,
+                };
+
+                return Some(SilkFrame::from_stream(self.data, env, prev_log_gain));
+            }
+        }
+
+        None
+    }
+
+    /// Decodes and returns the next primary (non-redundant) frame, or `None` once all `frames`
+    /// have been decoded.
+    fn next_primary(&mut self) -> Option<SilkFrame> {
         use self::frame::SilkFrameEnvironment;
 
         if self.cur_frame < self.frames {
             let channel = Channel::new(self.lp_header, self.cur_frame);
-            // FIXME temporarily assume that LBRR frames don't exist
-            let lbrr = false;
-
-            let frame = SilkFrameEnvironment {
+            let env = SilkFrameEnvironment {
                 channel,
-                lbrr,
+                frame_no: self.cur_frame,
+                lbrr: false,
+                lp_header: &self.lp_header,
                 stereo: self.stereo,
-                vad: self.lp_header.channel(channel).unwrap().vad(self.cur_frame),
-            }
-            .frame_from_stream(self.data);
+                subframes: self.subframes,
+            };
+            let prev_log_gain = match channel {
+                Channel::Mid => &mut *self.mid_prev_log_gain,
+                Channel::Side => &mut *self.side_prev_log_gain,
+            };
 
             self.cur_frame += 1;
-            Some(frame)
+            Some(SilkFrame::from_stream(self.data, env, prev_log_gain))
         } else {
             None
         }
     }
+}
+
+impl<'a, 'b, 'c> Iterator for SilkPacket<'a, 'b, 'c> {
+    type Item = SilkFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_lbrr().or_else(|| self.next_primary())
+    }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
         unimplemented!()
     }
 }
 
-impl ExactSizeIterator for SilkPacket<'_, '_> {}
-impl FusedIterator for SilkPacket<'_, '_> {}
+impl ExactSizeIterator for SilkPacket<'_, '_, '_> {}
+impl FusedIterator for SilkPacket<'_, '_, '_> {}
+
+/// Per-channel packet-loss concealment state, carried across packets so a lost frame can be
+/// extrapolated from the last real decode instead of falling back to silence outright.
+///
+/// Neither SILK's LPC coefficients nor its long-term (pitch) predictor state are threaded out of
+/// `SilkFrame` yet, so concealment here falls back to attenuated white noise scaled off the last
+/// real frame's own gain, rather than true LPC-domain resynthesis for voiced frames. That's RFC
+/// 6716 § 4.4's prescribed approach for unvoiced/inactive losses already, and a reasonable
+/// stand-in for voiced ones until the rest of the synthesis pipeline threads its own state through
+/// here.
+#[derive(Debug, Clone, Copy, Default)]
+struct PlcState {
+    /// Set once a real frame has been observed; `next_gain_q16` conceals with silence before
+    /// that and once the loss run exceeds `MAX_LOSSES`.
+    signal_type: Option<SignalType>,
+    /// Q16 gain to synthesize the next concealed frame's noise at, seeded from the last real
+    /// frame's own last-subframe gain.
+    gain_q16: i32,
+    /// Consecutive lost frames concealed since the last real decode.
+    loss_count: u8,
+}
+
+impl PlcState {
+    /// Roughly -3 dB, applied to the concealment gain for every lost frame after the first.
+    const ATTENUATION_Q16: i32 = 46_341;
+
+    /// Consecutive lost frames concealed before the channel is fully muted.
+    const MAX_LOSSES: u8 = 5;
+
+    /// Records that a real frame with `signal_type` and last-subframe gain `gain_q16` was just
+    /// decoded, resetting the concealment gain and loss run for the next time this channel is
+    /// lost.
+    fn observe(&mut self, signal_type: SignalType, gain_q16: i32) {
+        self.signal_type = Some(signal_type);
+        self.gain_q16 = gain_q16;
+        self.loss_count = 0;
+    }
+
+    /// Returns the Q16 gain to synthesize the next lost frame's concealment noise at, advancing
+    /// the loss run, or `None` if no frame has been observed yet or the run has exceeded
+    /// `MAX_LOSSES`.
+    fn next_gain_q16(&mut self) -> Option<i32> {
+        self.signal_type?;
+        if self.loss_count >= Self::MAX_LOSSES {
+            return None;
+        }
+
+        if self.loss_count > 0 {
+            self.gain_q16 =
+                ((i64::from(self.gain_q16) * i64::from(Self::ATTENUATION_Q16)) >> 16) as i32;
+        }
+        self.loss_count += 1;
+
+        Some(self.gain_q16)
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct SilkDecoder {
     stereo: bool,
     stereo_pred_weights: StereoPredWeights,
+    mid_plc: PlcState,
+    side_plc: PlcState,
+    /// State of the linear congruential PRNG behind packet-loss concealment's comfort noise.
+    plc_rng: u32,
+    /// The last-decoded subframe's log-gain for the mid channel, carried across packets for
+    /// [`SilkPacket`]'s independent-gain clamp. `None` before the first real frame is decoded.
+    ///
+    /// [`SilkPacket`]: struct.SilkPacket.html
+    mid_prev_log_gain: Option<u8>,
+    /// Same as `mid_prev_log_gain`, for the side channel.
+    side_prev_log_gain: Option<u8>,
 }
 
 impl SilkDecoder {
@@ -226,6 +396,11 @@ impl SilkDecoder {
         SilkDecoder {
             stereo,
             stereo_pred_weights: StereoPredWeights::default(),
+            mid_plc: PlcState::default(),
+            side_plc: PlcState::default(),
+            plc_rng: 1,
+            mid_prev_log_gain: None,
+            side_prev_log_gain: None,
         }
     }
 
@@ -235,10 +410,107 @@ impl SilkDecoder {
         config: Config,
         stereo: bool,
     ) -> Result<(), SilkError> {
-        let mut silk_packet = SilkPacket::from_stream(data, config, stereo)?;
-        let frame0 = silk_packet.next().unwrap();
+        let mut silk_packet = SilkPacket::from_stream(
+            data,
+            config,
+            stereo,
+            &mut self.mid_prev_log_gain,
+            &mut self.side_prev_log_gain,
+        )?;
+
+        for frame in &mut silk_packet {
+            match frame.channel() {
+                Channel::Mid => self
+                    .mid_plc
+                    .observe(frame.signal_type(), frame.gains().last_q16()),
+                Channel::Side => self
+                    .side_plc
+                    .observe(frame.signal_type(), frame.gains().last_q16()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Synthesizes concealment audio for one lost frame of `samples_per_channel` samples per
+    /// channel, interleaved if this decoder is running in stereo, from the signal type and gain
+    /// `PlcState::observe` retained since the last real decode.
+    pub(crate) fn conceal(&mut self, samples_per_channel: usize) -> Vec<i16> {
+        let mid_gain_q16 = self.mid_plc.next_gain_q16();
+        let side_gain_q16 = if self.stereo {
+            self.side_plc.next_gain_q16()
+        } else {
+            None
+        };
+
+        let channels = if self.stereo { 2 } else { 1 };
+        let mut out = Vec::with_capacity(samples_per_channel * channels);
+        for _ in 0..samples_per_channel {
+            out.push(Self::noise_sample(mid_gain_q16, &mut self.plc_rng));
+            if self.stereo {
+                out.push(Self::noise_sample(side_gain_q16, &mut self.plc_rng));
+            }
+        }
+
+        out
+    }
+
+    /// Returns the next random-sign comfort-noise sample at `gain_q16`, or silence if `gain_q16`
+    /// is `None`, advancing `rng`.
+    fn noise_sample(gain_q16: Option<i32>, rng: &mut u32) -> i16 {
+        let gain_q16 = match gain_q16 {
+            Some(gain_q16) => gain_q16,
+            None => return 0,
+        };
+
+        // a standard 32-bit LCG; only the sign of the high bit is used
+        *rng = rng.wrapping_mul(196_314_165).wrapping_add(907_633_515);
+        // `gain_q16` now comes straight from a real decoded subframe (see `SubframeGains`), whose
+        // range runs far above the comfort-noise gains this was originally tuned for—clamp before
+        // scaling so an unusually loud subframe can't wrap the cast into garbage instead of just
+        // clipping.
+        let magnitude =
+            ((i64::from(gain_q16) * i64::from(i16::MAX)) >> 16).min(i64::from(i16::MAX));
+
+        if *rng & 0x8000_0000 != 0 {
+            -magnitude as i16
+        } else {
+            magnitude as i16
+        }
+    }
+
+    /// Decodes the Low Bit-Rate Redundancy copy of the *previous* packet, embedded at the front
+    /// of this packet's SILK payload, in place of this packet's own primary frames.
+    ///
+    /// Callers use this once a packet is known to have been lost and the following packet has
+    /// arrived, observing the embedded LBRR copy's signal type into the concealment state this
+    /// decoder would otherwise only update from a real decode—a better basis for the next lost
+    /// frame's concealment than guessing from whatever the channel looked like two packets ago.
+    pub(crate) fn decode_fec(
+        &mut self,
+        data: &mut RangeDecoder<'_>,
+        config: Config,
+        stereo: bool,
+    ) -> Result<(), SilkError> {
+        let mut silk_packet = SilkPacket::from_stream(
+            data,
+            config,
+            stereo,
+            &mut self.mid_prev_log_gain,
+            &mut self.side_prev_log_gain,
+        )?;
+
+        while let Some(frame) = silk_packet.next_lbrr() {
+            match frame.channel() {
+                Channel::Mid => self
+                    .mid_plc
+                    .observe(frame.signal_type(), frame.gains().last_q16()),
+                Channel::Side => self
+                    .side_plc
+                    .observe(frame.signal_type(), frame.gains().last_q16()),
+            }
+        }
 
-        println!("{:?}\n{:?}", silk_packet, frame0);
         Ok(())
     }
 }
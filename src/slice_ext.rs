@@ -1,20 +1,23 @@
 //! Convienence methods on slices.
 
-use std::{
-    error::Error,
+use core::{
     fmt::{self, Display, Formatter},
     slice::SliceIndex,
 };
 
+#[cfg(feature = "std")]
+use std::error::Error;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default)]
 pub(crate) struct BoundsError;
 
 impl Display for BoundsError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(self.description())
+        f.write_str("out of bounds")
     }
 }
 
+#[cfg(feature = "std")]
 impl Error for BoundsError {
     fn description(&self) -> &str {
         "out of bounds"
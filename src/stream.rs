@@ -0,0 +1,145 @@
+//! Driving a `packet::Decoder` incrementally over a source of packets.
+
+use crate::{
+    error::Result,
+    packet::{Decoder, Packet},
+};
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
+
+/// Decodes a sequence of Opus packets pulled from `I` into PCM sample frames, one packet at a
+/// time.
+///
+/// Each item yielded by `I` is `Ok(Some(packet))` for a packet to decode normally, `Ok(None)` for
+/// a packet the source knows was lost (triggering packet-loss concealment in its place), or
+/// `Err(_)` if the source itself failed; `I` ending signals the end of the stream.
+///
+/// This can't be a [`std::iter::Iterator`], since the decoded frame borrows from the internal
+/// sample buffer; pull frames with [`StreamingDecoder::next_frame`] instead.
+///
+/// [`std::iter::Iterator`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html
+/// [`StreamingDecoder::next_frame`]: #method.next_frame
+#[derive(Debug)]
+pub struct StreamingDecoder<I> {
+    source: I,
+    decoder: Decoder,
+    buf: Vec<i16>,
+    pos: usize,
+}
+
+impl<I> StreamingDecoder<I>
+where
+    I: Iterator<Item = Result<Option<Packet>>>,
+{
+    /// Creates a streaming decoder pulling packets from `source`, outputting PCM at
+    /// `sample_rate` with `channels` channels.
+    pub fn new(source: I, sample_rate: u32, channels: u8) -> StreamingDecoder<I> {
+        StreamingDecoder {
+            source,
+            decoder: Decoder::new(sample_rate, channels),
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Returns the next frame of interleaved PCM samples, or `None` once `source` is exhausted.
+    ///
+    /// Only pulls and decodes another packet once the previously-returned frame has been fully
+    /// drained via [`StreamingDecoder::consume`].
+    ///
+    /// [`StreamingDecoder::consume`]: #method.consume
+    pub fn next_frame(&mut self) -> Option<Result<&[i16]>> {
+        if self.pos >= self.buf.len() {
+            let packet = match self.source.next()? {
+                Ok(packet) => packet,
+                Err(err) => return Some(Err(err)),
+            };
+
+            self.buf.clear();
+            if let Err(err) = self.decoder.decode(packet, &mut self.buf) {
+                return Some(Err(err));
+            }
+            self.pos = 0;
+        }
+
+        Some(Ok(&self.buf[self.pos..]))
+    }
+
+    /// Marks `samples` samples of the frame last returned by [`StreamingDecoder::next_frame`] as
+    /// consumed.
+    ///
+    /// [`StreamingDecoder::next_frame`]: #method.next_frame
+    pub fn consume(&mut self, samples: usize) {
+        self.pos = usize::min(self.pos + samples, self.buf.len());
+    }
+}
+
+/// Adapts a [`StreamingDecoder`] into a [`std::io::Read`] of interleaved little-endian PCM
+/// samples, so it can be fed directly into [`std::io::copy`].
+///
+/// [`StreamingDecoder`]: struct.StreamingDecoder.html
+/// [`std::io::Read`]: https://doc.rust-lang.org/stable/std/io/trait.Read.html
+/// [`std::io::copy`]: https://doc.rust-lang.org/stable/std/io/fn.copy.html
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct PcmReader<I> {
+    decoder: StreamingDecoder<I>,
+    /// Byte offset into the not-yet-read first sample of the current frame; 0 or 1, since samples
+    /// are 2 bytes wide.
+    byte_pos: u8,
+}
+
+#[cfg(feature = "std")]
+impl<I> PcmReader<I>
+where
+    I: Iterator<Item = Result<Option<Packet>>>,
+{
+    /// Wraps `decoder` as a byte source of interleaved little-endian `i16` samples.
+    pub fn new(decoder: StreamingDecoder<I>) -> PcmReader<I> {
+        PcmReader {
+            decoder,
+            byte_pos: 0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I> std::io::Read for PcmReader<I>
+where
+    I: Iterator<Item = Result<Option<Packet>>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use std::io::{Error, ErrorKind};
+
+        let mut written = 0;
+        while written < buf.len() {
+            let frame = match self.decoder.next_frame() {
+                None => break,
+                Some(Err(err)) => return Err(Error::new(ErrorKind::Other, err)),
+                Some(Ok(frame)) => frame,
+            };
+
+            let sample = match frame.first() {
+                Some(sample) => sample,
+                // the decoder produced an empty frame; avoid spinning forever on it
+                None => break,
+            };
+
+            let bytes = sample.to_le_bytes();
+            let avail = &bytes[usize::from(self.byte_pos)..];
+            let take = avail.len().min(buf.len() - written);
+            buf[written..written + take].copy_from_slice(&avail[..take]);
+            written += take;
+
+            if take == avail.len() {
+                self.decoder.consume(1);
+                self.byte_pos = 0;
+            } else {
+                self.byte_pos += take as u8;
+            }
+        }
+
+        Ok(written)
+    }
+}
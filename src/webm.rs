@@ -0,0 +1,1110 @@
+//! Decoding of WebM (Matroska)-encapsulated Opus streams.
+#![cfg(all(feature = "webm", feature = "ogg", feature = "std"))]
+
+use crate::{
+    error::{Error, Result},
+    ogg::{ElidedStruct, IdHeader},
+    packet::{Decoder as PacketDecoder, Packet as OpusPacket},
+    slice_ext::SliceExt,
+};
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    error,
+    fmt::{self, Debug, Formatter},
+    io::{prelude::*, SeekFrom},
+    num::NonZeroU32,
+};
+
+/// The Opus decoder always outputs samples at 48 kHz, regardless of the encoding sample rate.
+const DECODE_SAMPLE_RATE: u32 = 48_000;
+
+/// EBML element ID of the `EBML` header itself.
+const ID_EBML: u64 = 0x1A45_DFA3;
+/// EBML element ID of the top-level `Segment`.
+const ID_SEGMENT: u64 = 0x1853_8067;
+/// EBML element ID of `Segment`'s `Tracks` child.
+const ID_TRACKS: u64 = 0x1654_AE6B;
+/// EBML element ID of a `Tracks`' `TrackEntry` child.
+const ID_TRACK_ENTRY: u64 = 0xAE;
+/// EBML element ID of a `TrackEntry`'s `TrackNumber` child.
+const ID_TRACK_NUMBER: u64 = 0xD7;
+/// EBML element ID of a `TrackEntry`'s `CodecID` child.
+const ID_CODEC_ID: u64 = 0x86;
+/// EBML element ID of a `TrackEntry`'s `CodecPrivate` child.
+const ID_CODEC_PRIVATE: u64 = 0x63A2;
+/// EBML element ID of a `TrackEntry`'s `CodecDelay` child: the number of nanoseconds to discard
+/// from the start of the decoded track, the WebM analogue of an `OpusHead`'s pre-skip.
+const ID_CODEC_DELAY: u64 = 0x56AA;
+/// EBML element ID of a `TrackEntry`'s `SeekPreRoll` child: the number of nanoseconds of audio a
+/// decoder must discard output for (but still feed packets through) after seeking into the track.
+const ID_SEEK_PRE_ROLL: u64 = 0x56BB;
+/// EBML element ID of a `Segment`'s `Tags` child.
+const ID_TAGS: u64 = 0x1254_C367;
+/// EBML element ID of a `Tags`' `Tag` child.
+const ID_TAG: u64 = 0x7373;
+/// EBML element ID of a `Tag`'s `SimpleTag` child.
+const ID_SIMPLE_TAG: u64 = 0x67C8;
+/// EBML element ID of a `SimpleTag`'s `TagName` child.
+const ID_TAG_NAME: u64 = 0x45A3;
+/// EBML element ID of a `SimpleTag`'s `TagString` child.
+const ID_TAG_STRING: u64 = 0x4487;
+/// EBML element ID of a `Segment`'s `Cluster` children.
+const ID_CLUSTER: u64 = 0x1F43_B675;
+/// EBML element ID of a `Cluster`'s `Timecode` child.
+const ID_TIMECODE: u64 = 0xE7;
+/// EBML element ID of a `Cluster`'s `Position` child.
+const ID_POSITION: u64 = 0xA7;
+/// EBML element ID of a `Cluster`'s `PrevSize` child.
+const ID_PREV_SIZE: u64 = 0xAB;
+/// EBML element ID of a `Cluster`'s `SimpleBlock` children.
+const ID_SIMPLE_BLOCK: u64 = 0xA3;
+/// EBML element ID of a `Cluster`'s `BlockGroup` children.
+const ID_BLOCK_GROUP: u64 = 0xA0;
+/// EBML element ID of a `BlockGroup`'s `Block` child.
+const ID_BLOCK: u64 = 0xA1;
+
+/// The `CodecID` value identifying an Opus audio track.
+const CODEC_ID_OPUS: &[u8] = b"A_OPUS";
+
+/// The lacing mode bits within a `SimpleBlock`/`Block`'s flags byte.
+const LACING_MASK: u8 = 0b0000_0110;
+const LACING_NONE: u8 = 0b0000_0000;
+const LACING_XIPH: u8 = 0b0000_0010;
+const LACING_FIXED: u8 = 0b0000_0100;
+const LACING_EBML: u8 = 0b0000_0110;
+
+/// The error type returned when the WebM container or its embedded Opus track is malformed.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum WebMError {
+    /// The stream didn't begin with an `EBML` header, or its top-level element wasn't a
+    /// `Segment`.
+    BadMagic,
+    /// An EBML element's ID, size, or lacing couldn't be parsed.
+    MalformedElement,
+    /// No `Tracks` element, or no track with `CodecID` `A_OPUS`, could be found in the `Segment`.
+    NoOpusTrack,
+    /// The stream multiplexes more than one elementary Opus stream per packet, which
+    /// [`WebMOpusReader::read_samples`] doesn't support yet.
+    ///
+    /// [`WebMOpusReader::read_samples`]: struct.WebMOpusReader.html#method.read_samples
+    MultistreamUnsupported,
+    /// The underlying stream doesn't support seeking.
+    NotSeekable,
+}
+
+impl fmt::Display for WebMError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WebMError::BadMagic => "invalid EBML/Matroska magic",
+            WebMError::MalformedElement => "malformed EBML element",
+            WebMError::NoOpusTrack => "no Opus audio track found",
+            WebMError::MultistreamUnsupported => "multistream opus is not yet supported",
+            WebMError::NotSeekable => "stream is not seekable",
+        })
+    }
+}
+
+impl error::Error for WebMError {}
+
+/// The location of an EBML element found while scanning a WebM stream.
+#[derive(Debug, Clone, Copy)]
+struct ElementHeader {
+    id: u64,
+    /// Byte offset of the first content byte.
+    start: u64,
+    /// Byte offset just past the content, or `None` for an EBML "unknown size" element (common
+    /// for `Segment`/`Cluster` elements in streamed files).
+    end: Option<u64>,
+}
+
+/// Reads a single EBML variable-length integer (vint), returning its value and width in bytes.
+///
+/// If `keep_marker` is `false` (as for element sizes), the leading length-descriptor bit is
+/// masked out of the returned value; if `true` (as for element IDs), it's left in place, since
+/// EBML IDs are conventionally compared including their length marker.
+fn read_vint<R: Read>(reader: &mut R, keep_marker: bool) -> Result<(u64, u8)> {
+    let mut buf = [0u8; 8];
+    reader
+        .read_exact(&mut buf[..1])
+        .map_err(|_| Error::UnexpectedEof)?;
+
+    let width = 1 + buf[0].leading_zeros();
+    if width > 8 {
+        return Err(WebMError::MalformedElement.into());
+    }
+    let width = width as u8;
+
+    reader
+        .read_exact(&mut buf[1..usize::from(width)])
+        .map_err(|_| Error::UnexpectedEof)?;
+
+    let mut value = u64::from(if keep_marker {
+        buf[0]
+    } else {
+        buf[0] & 0xFFu8.checked_shr(u32::from(width)).unwrap_or(0)
+    });
+    for &byte in &buf[1..usize::from(width)] {
+        value = (value << 8) | u64::from(byte);
+    }
+
+    Ok((value, width))
+}
+
+/// Reads the EBML element header (ID and size) at the reader's current position.
+fn read_element_header<R: Read + Seek>(reader: &mut R) -> Result<ElementHeader> {
+    let (id, _) = read_vint(reader, true)?;
+    let (size, width) = read_vint(reader, false)?;
+    let start = reader
+        .seek(SeekFrom::Current(0))
+        .map_err(|_| WebMError::NotSeekable)?;
+
+    let unknown_size = size == (1u64 << (7 * u64::from(width))) - 1;
+    Ok(ElementHeader {
+        id,
+        start,
+        end: if unknown_size {
+            None
+        } else {
+            Some(start + size)
+        },
+    })
+}
+
+/// Reads `len` bytes of element content as a big-endian unsigned integer.
+fn read_uint<R: Read>(reader: &mut R, len: u64) -> Result<u64> {
+    let len = usize::try_from(len).map_err(|_| WebMError::MalformedElement)?;
+    if len > 8 {
+        return Err(WebMError::MalformedElement.into());
+    }
+
+    let mut buf = [0u8; 8];
+    reader
+        .read_exact(&mut buf[8 - len..])
+        .map_err(|_| Error::UnexpectedEof)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+/// Reads `len` bytes of element content verbatim.
+fn read_bytes<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>> {
+    let len = usize::try_from(len).map_err(|_| WebMError::MalformedElement)?;
+    let mut buf = vec![0u8; len];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|_| Error::UnexpectedEof)?;
+    Ok(buf)
+}
+
+/// Searches `parent`'s children for the first element whose ID is `wanted`, leaving the reader
+/// positioned at the start of its content if found.
+fn find_child<R: Read + Seek>(
+    reader: &mut R,
+    parent_start: u64,
+    parent_end: Option<u64>,
+    wanted: u64,
+) -> Result<Option<ElementHeader>> {
+    reader
+        .seek(SeekFrom::Start(parent_start))
+        .map_err(|_| WebMError::NotSeekable)?;
+
+    loop {
+        if let Some(end) = parent_end {
+            if reader
+                .seek(SeekFrom::Current(0))
+                .map_err(|_| WebMError::NotSeekable)?
+                >= end
+            {
+                return Ok(None);
+            }
+        }
+
+        let header = match read_element_header(reader) {
+            Ok(header) => header,
+            // ran out of bytes before finding `wanted`; there's nothing more to search
+            Err(_) if parent_end.is_none() => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        if header.id == wanted {
+            return Ok(Some(header));
+        }
+
+        match header.end {
+            Some(end) => {
+                reader
+                    .seek(SeekFrom::Start(end))
+                    .map_err(|_| WebMError::NotSeekable)?;
+            }
+            // an unknown-size child that isn't the element being searched for; its true extent
+            // can't be determined without parsing its contents, which isn't needed here
+            None => return Err(WebMError::MalformedElement.into()),
+        }
+    }
+}
+
+/// The `TrackEntry` fields needed to decode its Opus track.
+struct OpusTrack {
+    number: u64,
+    codec_private: Vec<u8>,
+    /// Nanoseconds to discard from the start of the track, or `0` if absent.
+    codec_delay: u64,
+    /// Nanoseconds of output to discard after seeking into the track, or `0` if absent.
+    seek_pre_roll: u64,
+}
+
+/// Parses a `TrackEntry`'s children, returning its [`OpusTrack`] fields if it's an Opus audio
+/// track.
+fn parse_track_entry<R: Read + Seek>(
+    reader: &mut R,
+    entry: ElementHeader,
+) -> Result<Option<OpusTrack>> {
+    reader
+        .seek(SeekFrom::Start(entry.start))
+        .map_err(|_| WebMError::NotSeekable)?;
+
+    let (mut track_number, mut codec_id, mut codec_private) = (None, None, None);
+    let (mut codec_delay, mut seek_pre_roll) = (0, 0);
+    loop {
+        if let Some(end) = entry.end {
+            if reader
+                .seek(SeekFrom::Current(0))
+                .map_err(|_| WebMError::NotSeekable)?
+                >= end
+            {
+                break;
+            }
+        }
+
+        let header = match read_element_header(reader) {
+            Ok(header) => header,
+            Err(_) if entry.end.is_none() => break,
+            Err(err) => return Err(err),
+        };
+        let len = header
+            .end
+            .map(|end| end - header.start)
+            .ok_or(WebMError::MalformedElement)?;
+
+        match header.id {
+            ID_TRACK_NUMBER => track_number = Some(read_uint(reader, len)?),
+            ID_CODEC_ID => codec_id = Some(read_bytes(reader, len)?),
+            ID_CODEC_PRIVATE => codec_private = Some(read_bytes(reader, len)?),
+            ID_CODEC_DELAY => codec_delay = read_uint(reader, len)?,
+            ID_SEEK_PRE_ROLL => seek_pre_roll = read_uint(reader, len)?,
+            _ => {}
+        }
+
+        reader
+            .seek(SeekFrom::Start(header.start + len))
+            .map_err(|_| WebMError::NotSeekable)?;
+    }
+
+    match (track_number, codec_id, codec_private) {
+        (Some(number), Some(codec_id), Some(codec_private)) if codec_id == CODEC_ID_OPUS => {
+            Ok(Some(OpusTrack {
+                number,
+                codec_private,
+                codec_delay,
+                seek_pre_roll,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Searches `tracks` for the first `TrackEntry` with `CodecID` `A_OPUS`, returning its
+/// [`OpusTrack`] fields.
+fn find_opus_track<R: Read + Seek>(reader: &mut R, tracks: ElementHeader) -> Result<OpusTrack> {
+    reader
+        .seek(SeekFrom::Start(tracks.start))
+        .map_err(|_| WebMError::NotSeekable)?;
+
+    loop {
+        if let Some(end) = tracks.end {
+            if reader
+                .seek(SeekFrom::Current(0))
+                .map_err(|_| WebMError::NotSeekable)?
+                >= end
+            {
+                return Err(WebMError::NoOpusTrack.into());
+            }
+        }
+
+        let header = match read_element_header(reader) {
+            Ok(header) => header,
+            Err(_) if tracks.end.is_none() => return Err(WebMError::NoOpusTrack.into()),
+            Err(err) => return Err(err),
+        };
+
+        if header.id == ID_TRACK_ENTRY {
+            if let Some(found) = parse_track_entry(reader, header)? {
+                return Ok(found);
+            }
+        }
+
+        match header.end {
+            Some(end) => {
+                reader
+                    .seek(SeekFrom::Start(end))
+                    .map_err(|_| WebMError::NotSeekable)?;
+            }
+            None => return Err(WebMError::MalformedElement.into()),
+        }
+    }
+}
+
+/// Reads a vint from `data` at `*pos`, advancing `*pos` past it. See [`read_vint`].
+///
+/// [`read_vint`]: fn.read_vint.html
+fn read_vint_slice(data: &[u8], pos: &mut usize, keep_marker: bool) -> Result<(u64, u8)> {
+    let first = *data.get_res(*pos)?;
+    let width = 1 + first.leading_zeros();
+    if width > 8 {
+        return Err(WebMError::MalformedElement.into());
+    }
+    let width = width as u8;
+
+    let mut value = u64::from(if keep_marker {
+        first
+    } else {
+        first & 0xFFu8.checked_shr(u32::from(width)).unwrap_or(0)
+    });
+    for &byte in data.get_res(*pos + 1..*pos + usize::from(width))? {
+        value = (value << 8) | u64::from(byte);
+    }
+
+    *pos += usize::from(width);
+    Ok((value, width))
+}
+
+/// Reads the EBML element header (ID and size) at `*pos` within `data`, advancing `*pos` to the
+/// start of its content. See [`read_element_header`].
+///
+/// [`read_element_header`]: fn.read_element_header.html
+fn read_element_header_slice(data: &[u8], pos: &mut usize) -> Result<(u64, usize, usize)> {
+    let (id, _) = read_vint_slice(data, pos, true)?;
+    let (size, _) = read_vint_slice(data, pos, false)?;
+    let size = usize::try_from(size).map_err(|_| WebMError::MalformedElement)?;
+
+    let start = *pos;
+    let end = start.checked_add(size).ok_or(WebMError::MalformedElement)?;
+    Ok((id, start, end))
+}
+
+/// Splits a laced `SimpleBlock`/`Block` body (past its lacing-mode frame count) into its
+/// individual frames.
+fn split_laced_frames(mut data: &[u8], lacing: u8) -> Result<Vec<Vec<u8>>> {
+    if lacing == LACING_NONE {
+        return Ok(vec![data.to_owned()]);
+    }
+
+    let (&frame_count, rest) = data.split_first_res()?;
+    data = rest;
+    let frame_count = usize::from(frame_count) + 1;
+
+    let mut sizes = Vec::with_capacity(frame_count - 1);
+    match lacing {
+        LACING_XIPH => {
+            for _ in 0..frame_count - 1 {
+                let mut size = 0usize;
+                loop {
+                    let (&byte, rest) = data.split_first_res()?;
+                    data = rest;
+                    size += usize::from(byte);
+                    if byte != 0xFF {
+                        break;
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+        LACING_EBML => {
+            let mut pos = 0;
+            let (first, _) = read_vint_slice(data, &mut pos, false)?;
+            data = data.get_res(pos..)?;
+            sizes.push(usize::try_from(first).map_err(|_| WebMError::MalformedElement)?);
+
+            for _ in 0..frame_count.saturating_sub(2) {
+                let mut pos = 0;
+                let (raw, width) = read_vint_slice(data, &mut pos, false)?;
+                data = data.get_res(pos..)?;
+
+                let bias = (1i64 << (7 * u64::from(width) - 1)) - 1;
+                let prev = *sizes.last().ok_or(WebMError::MalformedElement)? as i64;
+                let size = prev + (raw as i64 - bias);
+                sizes.push(usize::try_from(size).map_err(|_| WebMError::MalformedElement)?);
+            }
+        }
+        LACING_FIXED => {}
+        _ => return Err(WebMError::MalformedElement.into()),
+    }
+
+    let mut frames = Vec::with_capacity(frame_count);
+    if lacing == LACING_FIXED {
+        let size = data.len() / frame_count;
+        for _ in 0..frame_count {
+            let (frame, rest) = (data.get_res(..size)?, data.get_res(size..)?);
+            frames.push(frame.to_owned());
+            data = rest;
+        }
+    } else {
+        for &size in &sizes {
+            let (frame, rest) = (data.get_res(..size)?, data.get_res(size..)?);
+            frames.push(frame.to_owned());
+            data = rest;
+        }
+        frames.push(data.to_owned());
+    }
+
+    Ok(frames)
+}
+
+/// Splits a `SimpleBlock`/`Block` element's content into its constituent frames, if it belongs to
+/// `track_number`.
+fn parse_block(data: &[u8], track_number: u64) -> Result<Option<Vec<Vec<u8>>>> {
+    let mut pos = 0;
+    let (number, _) = read_vint_slice(data, &mut pos, false)?;
+    pos += 2; // relative timecode; unused, since we only decode sequentially
+    let flags = *data.get_res(pos)?;
+    pos += 1;
+
+    if number != track_number {
+        return Ok(None);
+    }
+
+    Ok(Some(split_laced_frames(
+        data.get_res(pos..)?,
+        flags & LACING_MASK,
+    )?))
+}
+
+/// Scans a `Cluster`'s children for the next `SimpleBlock`/`Block` belonging to `track_number`.
+///
+/// On success or failure to find one, `*scan_pos` is left at the point scanning should resume
+/// from (either just past the found block, or at the first child that didn't belong to the
+/// `Cluster`, so the caller can re-read it as a sibling of the `Cluster` itself).
+fn scan_cluster<R: Read + Seek>(
+    reader: &mut R,
+    cluster: ElementHeader,
+    track_number: u64,
+    scan_pos: &mut u64,
+) -> Result<Option<Vec<Vec<u8>>>> {
+    let mut pos = cluster.start;
+
+    loop {
+        if let Some(end) = cluster.end {
+            if pos >= end {
+                *scan_pos = end;
+                return Ok(None);
+            }
+        }
+
+        reader
+            .seek(SeekFrom::Start(pos))
+            .map_err(|_| WebMError::NotSeekable)?;
+        let child = match read_element_header(reader) {
+            Ok(child) => child,
+            Err(_) => {
+                *scan_pos = pos;
+                return Ok(None);
+            }
+        };
+
+        match child.id {
+            ID_SIMPLE_BLOCK => {
+                let len = child.end.ok_or(WebMError::MalformedElement)? - child.start;
+                let body = read_bytes(reader, len)?;
+                pos = child.start + len;
+
+                if let Some(frames) = parse_block(&body, track_number)? {
+                    *scan_pos = pos;
+                    return Ok(Some(frames));
+                }
+            }
+            ID_BLOCK_GROUP => {
+                let block = find_child(reader, child.start, child.end, ID_BLOCK)?;
+                pos = child.end.ok_or(WebMError::MalformedElement)?;
+
+                if let Some(block) = block {
+                    let len = block.end.ok_or(WebMError::MalformedElement)? - block.start;
+                    reader
+                        .seek(SeekFrom::Start(block.start))
+                        .map_err(|_| WebMError::NotSeekable)?;
+                    let body = read_bytes(reader, len)?;
+
+                    if let Some(frames) = parse_block(&body, track_number)? {
+                        *scan_pos = pos;
+                        return Ok(Some(frames));
+                    }
+                }
+            }
+            ID_TIMECODE | ID_POSITION | ID_PREV_SIZE => {
+                pos = child.end.ok_or(WebMError::MalformedElement)?;
+            }
+            // not a recognized `Cluster` child; it must be the next sibling of the `Cluster`
+            // itself (common for unknown-size `Cluster`s), so stop here without consuming it
+            _ => {
+                *scan_pos = pos;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Scans forward from `*scan_pos` for the next `SimpleBlock`/`Block` belonging to
+/// `track_number`, descending into `Cluster` elements as needed.
+fn next_block<R: Read + Seek>(
+    reader: &mut R,
+    track_number: u64,
+    scan_pos: &mut u64,
+    segment_end: Option<u64>,
+) -> Result<Option<Vec<Vec<u8>>>> {
+    loop {
+        if segment_end.map_or(false, |end| *scan_pos >= end) {
+            return Ok(None);
+        }
+
+        reader
+            .seek(SeekFrom::Start(*scan_pos))
+            .map_err(|_| WebMError::NotSeekable)?;
+        let header = match read_element_header(reader) {
+            Ok(header) => header,
+            Err(_) if segment_end.is_none() => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        if header.id != ID_CLUSTER {
+            *scan_pos = header.end.ok_or(WebMError::MalformedElement)?;
+            continue;
+        }
+
+        if let Some(frames) = scan_cluster(reader, header, track_number, scan_pos)? {
+            return Ok(Some(frames));
+        }
+    }
+}
+
+/// Scans a WebM stream for the raw Opus packets of a single track, shared by both [`Frames`] and
+/// [`Samples`].
+///
+/// [`Frames`]: struct.Frames.html
+/// [`Samples`]: struct.Samples.html
+struct RawFrames<R> {
+    reader: R,
+    track_number: u64,
+    segment_end: Option<u64>,
+    /// Byte offset to resume scanning for the next block from.
+    scan_pos: u64,
+    /// Frames sliced out of the most recently read (possibly laced) block, awaiting return.
+    pending: VecDeque<Vec<u8>>,
+}
+
+impl<R> RawFrames<R>
+where
+    R: Read + Seek,
+{
+    fn new(reader: R, track_number: u64, segment_end: Option<u64>, scan_pos: u64) -> RawFrames<R> {
+        RawFrames {
+            reader,
+            track_number,
+            segment_end,
+            scan_pos,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn next_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(frame) = self.pending.pop_front() {
+                return Ok(Some(frame));
+            }
+
+            match next_block(
+                &mut self.reader,
+                self.track_number,
+                &mut self.scan_pos,
+                self.segment_end,
+            )? {
+                Some(frames) => self.pending = frames.into_iter().collect(),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R> Debug for RawFrames<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawFrames")
+            .field("reader", &ElidedStruct("R"))
+            .field("track_number", &self.track_number)
+            .field("segment_end", &self.segment_end)
+            .field("scan_pos", &self.scan_pos)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+/// An iterator over the raw Opus packets of a [`WebMOpusReader`]'s audio track.
+///
+/// [`WebMOpusReader`]: struct.WebMOpusReader.html
+#[derive(Debug)]
+pub struct Frames<R> {
+    raw: RawFrames<R>,
+}
+
+impl<R> Iterator for Frames<R>
+where
+    R: Read + Seek,
+{
+    type Item = Result<OpusPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.raw.next_packet() {
+            Ok(Some(bytes)) => Some(OpusPacket::new(&bytes)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// An iterator over decoded PCM samples from a [`WebMOpusReader`].
+///
+/// [`WebMOpusReader`]: struct.WebMOpusReader.html
+pub struct Samples<R> {
+    raw: RawFrames<R>,
+    decoder: PacketDecoder,
+    buf: Vec<i16>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R> Samples<R>
+where
+    R: Read + Seek,
+{
+    fn new(
+        reader: R,
+        track_number: u64,
+        segment_end: Option<u64>,
+        scan_pos: u64,
+        id_header: &IdHeader,
+    ) -> Samples<R> {
+        let mapping_table = id_header.channels().mapping_table();
+        let channels =
+            mapping_table.coupled() * 2 + (mapping_table.streams() - mapping_table.coupled());
+
+        Samples {
+            raw: RawFrames::new(reader, track_number, segment_end, scan_pos),
+            decoder: PacketDecoder::new(DECODE_SAMPLE_RATE, channels),
+            buf: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R> Iterator for Samples<R>
+where
+    R: Read + Seek,
+{
+    type Item = Result<i16>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(&sample) = self.buf.get(self.pos) {
+                self.pos += 1;
+                return Some(Ok(sample));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            let packet_bytes = match self.raw.next_packet() {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => {
+                    self.done = true;
+                    continue;
+                }
+                Err(err) => return Some(Err(err)),
+            };
+
+            let opus_packet = match OpusPacket::new(&packet_bytes) {
+                Ok(opus_packet) => opus_packet,
+                Err(err) => return Some(Err(err)),
+            };
+
+            self.buf.clear();
+            if let Err(err) = self.decoder.decode(Some(opus_packet), &mut self.buf) {
+                return Some(Err(err));
+            }
+            self.pos = 0;
+        }
+    }
+}
+
+impl<R> Debug for Samples<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Samples")
+            .field("raw", &self.raw)
+            .field("decoder", &self.decoder)
+            .field("buf", &self.buf)
+            .field("pos", &self.pos)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// An iterator over `TagName`/`TagString` pairs from a WebM file's `Tags` element, the Matroska
+/// analogue of [`ogg::Comments`].
+///
+/// [`ogg::Comments`]: ../ogg/struct.Comments.html
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Comments<'a> {
+    tags: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Comments<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos >= self.tags.len() {
+                return None;
+            }
+
+            let (id, start, end) = read_element_header_slice(self.tags, &mut self.pos).ok()?;
+
+            if id == ID_TAG {
+                // descend into the `Tag`'s own children without skipping past them
+                continue;
+            }
+
+            if id != ID_SIMPLE_TAG {
+                self.pos = end;
+                continue;
+            }
+
+            self.pos = end;
+            let body = self.tags.get(start..end)?;
+
+            let (mut name, mut value) = (None, None);
+            let mut child_pos = 0;
+            while child_pos < body.len() {
+                let (child_id, c_start, c_end) =
+                    read_element_header_slice(body, &mut child_pos).ok()?;
+
+                match child_id {
+                    ID_TAG_NAME => name = std::str::from_utf8(body.get(c_start..c_end)?).ok(),
+                    ID_TAG_STRING => value = std::str::from_utf8(body.get(c_start..c_end)?).ok(),
+                    _ => {}
+                }
+
+                child_pos = c_end;
+            }
+
+            if let (Some(name), Some(value)) = (name, value) {
+                return Some((name, value));
+            }
+        }
+    }
+}
+
+/// A reader for Opus audio tracks embedded in WebM (Matroska) files and/or streams.
+pub struct WebMOpusReader<R> {
+    reader: R,
+    id_header: IdHeader,
+    track_number: u64,
+    /// Nanoseconds to discard from the start of the track, or `0` if the `TrackEntry` carried no
+    /// `CodecDelay`.
+    codec_delay: u64,
+    /// Nanoseconds of output to discard after seeking into the track, or `0` if the `TrackEntry`
+    /// carried no `SeekPreRoll`.
+    seek_pre_roll: u64,
+    /// The raw content of the `Segment`'s `Tags` element, if any, backing [`Self::comments`].
+    ///
+    /// [`Self::comments`]: #method.comments
+    tags: Vec<u8>,
+    segment_end: Option<u64>,
+    /// Byte offset to resume scanning for `Cluster`s from, once decoding begins.
+    scan_pos: u64,
+}
+
+impl<R> WebMOpusReader<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a new `WebMOpusReader` from the given reader, locating its first Opus audio track.
+    pub fn new(mut reader: R) -> Result<WebMOpusReader<R>> {
+        reader
+            .seek(SeekFrom::Start(0))
+            .map_err(|_| WebMError::NotSeekable)?;
+
+        let ebml = read_element_header(&mut reader)?;
+        if ebml.id != ID_EBML {
+            return Err(WebMError::BadMagic.into());
+        }
+        reader
+            .seek(SeekFrom::Start(
+                ebml.end.ok_or(WebMError::MalformedElement)?,
+            ))
+            .map_err(|_| WebMError::NotSeekable)?;
+
+        let segment = read_element_header(&mut reader)?;
+        if segment.id != ID_SEGMENT {
+            return Err(WebMError::BadMagic.into());
+        }
+
+        let tracks = find_child(&mut reader, segment.start, segment.end, ID_TRACKS)?
+            .ok_or(WebMError::NoOpusTrack)?;
+        let track = find_opus_track(&mut reader, tracks)?;
+        let id_header = IdHeader::new(&track.codec_private)?;
+
+        let tags = match find_child(&mut reader, segment.start, segment.end, ID_TAGS)? {
+            Some(tags) => {
+                let len = tags.end.ok_or(WebMError::MalformedElement)? - tags.start;
+                read_bytes(&mut reader, len)?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(WebMOpusReader {
+            reader,
+            id_header,
+            track_number: track.number,
+            codec_delay: track.codec_delay,
+            seek_pre_roll: track.seek_pre_roll,
+            tags,
+            segment_end: segment.end,
+            scan_pos: tracks.end.ok_or(WebMError::MalformedElement)?,
+        })
+    }
+
+    /// Returns an iterator over `TagName`/`TagString` pairs from the `Segment`'s `Tags` element.
+    #[inline]
+    pub fn comments(&self) -> Comments<'_> {
+        Comments {
+            tags: &self.tags[..],
+            pos: 0,
+        }
+    }
+
+    /// Returns an iterator over the contained raw Opus packets.
+    #[inline]
+    pub fn frames(self) -> Frames<R> {
+        Frames {
+            raw: RawFrames::new(
+                self.reader,
+                self.track_number,
+                self.segment_end,
+                self.scan_pos,
+            ),
+        }
+    }
+
+    /// Returns the number of samples (at 48 kHz) to discard when beginning playback.
+    ///
+    /// Prefers the track's `CodecDelay`, the WebM analogue of pre-skip, falling back to the
+    /// embedded `OpusHead`'s own pre-skip field if the track carried no `CodecDelay`.
+    #[inline]
+    pub fn pre_skip(&self) -> u16 {
+        if self.codec_delay == 0 {
+            return self.id_header.pre_skip();
+        }
+
+        let samples = self.codec_delay * u64::from(DECODE_SAMPLE_RATE) / 1_000_000_000;
+        u16::try_from(samples).unwrap_or(u16::max_value())
+    }
+
+    /// Returns the number of samples (at 48 kHz) of output a decoder must discard after seeking
+    /// into this track, derived from its `SeekPreRoll`.
+    #[inline]
+    pub fn seek_pre_roll(&self) -> u64 {
+        self.seek_pre_roll * u64::from(DECODE_SAMPLE_RATE) / 1_000_000_000
+    }
+
+    /// Returns the sample rate of the media this file was encoded from, in Hz.
+    ///
+    /// Note that this is not necessarily the sample rate it will be played back at.
+    #[inline]
+    pub fn sample_rate(&self) -> Option<NonZeroU32> {
+        self.id_header.sample_rate()
+    }
+
+    /// Returns 20&thinsp;log<sub>10</sub> of the factor by which to scale the decoder output to
+    /// receive the desired playback volume.
+    #[inline]
+    pub fn output_gain(&self) -> i16 {
+        self.id_header.output_gain()
+    }
+
+    /// Returns the number of output channels.
+    #[inline]
+    pub fn channels(&self) -> u8 {
+        self.id_header.channels().channels()
+    }
+
+    /// Returns the encapsulation specification version as (major, minor).
+    #[inline]
+    pub fn version(&self) -> (u8, u8) {
+        self.id_header.version()
+    }
+
+    /// Returns an iterator over decoded PCM samples.
+    ///
+    /// Only single-stream (non-multiplexed) Opus is supported so far; other streams return
+    /// [`WebMError::MultistreamUnsupported`].
+    ///
+    /// [`WebMError::MultistreamUnsupported`]: enum.WebMError.html#variant.MultistreamUnsupported
+    pub fn read_samples(self) -> Result<Samples<R>> {
+        if self.id_header.channels().mapping_table().streams() != 1 {
+            return Err(WebMError::MultistreamUnsupported.into());
+        }
+
+        Ok(Samples::new(
+            self.reader,
+            self.track_number,
+            self.segment_end,
+            self.scan_pos,
+            &self.id_header,
+        ))
+    }
+}
+
+impl<R> Debug for WebMOpusReader<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebMOpusReader")
+            .field("reader", &ElidedStruct("R"))
+            .field("id_header", &self.id_header)
+            .field("track_number", &self.track_number)
+            .field("codec_delay", &self.codec_delay)
+            .field("seek_pre_roll", &self.seek_pre_roll)
+            .field("tags", &self.tags)
+            .field("segment_end", &self.segment_end)
+            .field("scan_pos", &self.scan_pos)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_vint_strips_marker_unless_kept() {
+        // 1-byte vint 0b1_0101010 (0xAA): value 0x2A with marker masked, 0xAA with it kept
+        let mut reader = Cursor::new([0xAAu8]);
+        assert_eq!(read_vint(&mut reader, false).unwrap(), (0x2A, 1));
+
+        let mut reader = Cursor::new([0xAAu8]);
+        assert_eq!(read_vint(&mut reader, true).unwrap(), (0xAA, 1));
+    }
+
+    #[test]
+    fn read_vint_multibyte_width_from_leading_zeros() {
+        // 0x01 has 7 leading zero bits, so width is 8; id 0x1A45DFA3 is 4 bytes (width encoded in
+        // the first byte, 0x1A == 0b0001_1010 -> 3 leading zeros -> width 4)
+        let mut reader = Cursor::new([0x1A, 0x45, 0xDF, 0xA3]);
+        assert_eq!(read_vint(&mut reader, true).unwrap(), (ID_EBML, 4));
+    }
+
+    #[test]
+    fn read_vint_rejects_all_zero_first_byte() {
+        let mut reader = Cursor::new([0x00u8; 9]);
+        assert!(matches!(
+            read_vint(&mut reader, false),
+            Err(Error::WebM(WebMError::MalformedElement))
+        ));
+    }
+
+    #[test]
+    fn read_vint_slice_matches_reader_variant() {
+        let data = [0x1A, 0x45, 0xDF, 0xA3];
+        let mut pos = 0;
+        assert_eq!(
+            read_vint_slice(&data, &mut pos, true).unwrap(),
+            (ID_EBML, 4)
+        );
+        assert_eq!(pos, 4);
+    }
+
+    #[test]
+    fn read_vint_slice_reports_eof_past_the_end() {
+        let data = [0x1Au8];
+        let mut pos = 0;
+        assert!(read_vint_slice(&data, &mut pos, true).is_err());
+    }
+
+    #[test]
+    fn read_element_header_slice_computes_content_bounds() {
+        // ID 0xA3 (1 byte, marker kept) followed by size 0x02 (1-byte vint, marker stripped)
+        let data = [0xA3, 0x82, 0xAA, 0xBB];
+        let mut pos = 0;
+        let (id, start, end) = read_element_header_slice(&data, &mut pos).unwrap();
+        assert_eq!(id, 0xA3);
+        assert_eq!((start, end), (2, 4));
+    }
+
+    #[test]
+    fn split_laced_frames_no_lacing_returns_whole_body() {
+        let frames = split_laced_frames(&[1, 2, 3], LACING_NONE).unwrap();
+        assert_eq!(frames, vec![vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn split_laced_frames_fixed_splits_evenly() {
+        // frame count byte 2 (3 frames), 3 equal-size frames follow
+        let data = [2, 1, 1, 2, 2, 3, 3];
+        let frames = split_laced_frames(&data, LACING_FIXED).unwrap();
+        assert_eq!(frames, vec![vec![1, 1], vec![2, 2], vec![3, 3]]);
+    }
+
+    #[test]
+    fn split_laced_frames_xiph_sums_0xff_continuation_bytes() {
+        // frame count byte 1 (2 frames): first frame's size is 0xFF + 0x05 = 260, then the
+        // remainder is the second (implicit) frame
+        let mut data = vec![1, 0xFF, 0x05];
+        data.extend(vec![0xAAu8; 260]);
+        data.extend(vec![0xBBu8; 4]);
+        let frames = split_laced_frames(&data, LACING_XIPH).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], vec![0xAAu8; 260]);
+        assert_eq!(frames[1], vec![0xBBu8; 4]);
+    }
+
+    #[test]
+    fn split_laced_frames_rejects_unknown_lacing_mode() {
+        assert!(matches!(
+            split_laced_frames(&[0], 0b0000_0110 + 1),
+            Err(Error::WebM(WebMError::MalformedElement))
+        ));
+    }
+
+    #[test]
+    fn parse_block_returns_none_for_other_tracks() {
+        // track number vint 0x81 (=1), 2-byte relative timecode, flags byte (no lacing), 1 frame byte
+        let data = [0x81, 0x00, 0x00, 0x00, 0xAA];
+        assert!(parse_block(&data, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_block_splits_unlaced_frame_for_matching_track() {
+        let data = [0x81, 0x00, 0x00, 0x00, 0xAA, 0xBB];
+        let frames = parse_block(&data, 1).unwrap().unwrap();
+        assert_eq!(frames, vec![vec![0xAA, 0xBB]]);
+    }
+
+    #[test]
+    fn webm_error_display_is_human_readable() {
+        assert_eq!(
+            WebMError::BadMagic.to_string(),
+            "invalid EBML/Matroska magic"
+        );
+        assert_eq!(
+            WebMError::NoOpusTrack.to_string(),
+            "no Opus audio track found"
+        );
+    }
+}